@@ -0,0 +1,187 @@
+use image::{GenericImage, RgbaImage};
+use macroquad::{
+  math::Rect,
+  prelude::{Color, FilterMode},
+  texture::{draw_texture_ex, DrawTextureParams, Texture2D},
+};
+
+/** Largest sheet side in pixels. Images that do not fit start another sheet. */
+pub const SHEET_SIZE: u32 = 1024;
+/** Empty pixels between packed images, so filtering never samples a neighbour. */
+const PADDING: u32 = 1;
+
+/** Where one image ended up. */
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Place {
+  pub sheet: usize,
+  pub x: u32,
+  pub y: u32,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Packing {
+  /** One place per packed size, in the order they were given. */
+  pub places: Vec<Place>,
+  /** Width and height of every sheet. */
+  pub sheets: Vec<(u32, u32)>,
+}
+
+/**
+ * Packs `sizes` onto shelves of sheets at most `max_size` pixels wide and high, tallest images
+ * first. An image larger than `max_size` gets a sheet of its own.
+ */
+pub fn pack(sizes: &[(u32, u32)], max_size: u32, padding: u32) -> Packing {
+  let mut order: Vec<usize> = (0..sizes.len()).collect();
+  order.sort_by_key(|&i| (std::cmp::Reverse(sizes[i].1), std::cmp::Reverse(sizes[i].0)));
+
+  let mut places = vec![
+    Place {
+      sheet: 0,
+      x: 0,
+      y: 0
+    };
+    sizes.len()
+  ];
+  let mut sheets: Vec<(u32, u32)> = Vec::new();
+  //Cursor on the current shelf of the last sheet.
+  let (mut x, mut y, mut shelf_height) = (0, 0, 0);
+  for i in order {
+    let (w, h) = sizes[i];
+    if w > max_size || h > max_size {
+      sheets.push((w, h));
+      places[i] = Place {
+        sheet: sheets.len() - 1,
+        x: 0,
+        y: 0,
+      };
+      //Nothing else goes on that sheet.
+      (x, y, shelf_height) = (0, max_size, 0);
+      continue;
+    }
+    if sheets.is_empty() || x + w > max_size {
+      x = 0;
+      y += shelf_height + padding;
+      shelf_height = 0;
+    }
+    if sheets.is_empty() || y + h > max_size {
+      sheets.push((0, 0));
+      x = 0;
+      y = 0;
+      shelf_height = 0;
+    }
+
+    let sheet = sheets.len() - 1;
+    places[i] = Place { sheet, x, y };
+    sheets[sheet].0 = sheets[sheet].0.max(x + w);
+    sheets[sheet].1 = sheets[sheet].1.max(y + h);
+    x += w + padding;
+    shelf_height = shelf_height.max(h);
+  }
+  Packing { places, sheets }
+}
+
+/** One packed image: the sheet it is on and the part of the sheet it covers. */
+#[derive(Debug, Clone, Copy)]
+pub struct Frame {
+  pub texture: Texture2D,
+  pub source: Rect,
+  /** Drawn upside down, on top of any flip the caller asks for. */
+  pub flip_y: bool,
+}
+
+impl Frame {
+  /** `draw_texture_ex` limited to this frame's part of the sheet. */
+  pub fn draw(&self, x: f32, y: f32, color: Color, params: DrawTextureParams) {
+    draw_texture_ex(
+      self.texture,
+      x,
+      y,
+      color,
+      DrawTextureParams {
+        source: Some(self.source),
+        flip_y: params.flip_y != self.flip_y,
+        ..params
+      },
+    );
+  }
+}
+
+/** Packs `images` into sheet textures. Returns the sheets and one frame per image. */
+pub fn build(images: &[RgbaImage]) -> (Vec<Texture2D>, Vec<Frame>) {
+  let sizes: Vec<(u32, u32)> = images.iter().map(|i| i.dimensions()).collect();
+  let packing = pack(&sizes, SHEET_SIZE, PADDING);
+
+  let mut sheet_images: Vec<RgbaImage> = packing
+    .sheets
+    .iter()
+    .map(|&(w, h)| RgbaImage::new(w, h))
+    .collect();
+  for (image, place) in images.iter().zip(&packing.places) {
+    sheet_images[place.sheet]
+      .copy_from(image, place.x, place.y)
+      .expect("Packed image does not fit its sheet.");
+  }
+
+  let sheets: Vec<Texture2D> = sheet_images
+    .iter()
+    .map(|image| {
+      let tex = Texture2D::from_rgba8(image.width() as u16, image.height() as u16, image);
+      tex.set_filter(FilterMode::Nearest);
+      tex
+    })
+    .collect();
+  let frames = sizes
+    .iter()
+    .zip(&packing.places)
+    .map(|(&(w, h), place)| Frame {
+      texture: sheets[place.sheet],
+      source: Rect::new(place.x as f32, place.y as f32, w as f32, h as f32),
+      flip_y: false,
+    })
+    .collect();
+  (sheets, frames)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn overlaps(a: (Place, (u32, u32)), b: (Place, (u32, u32))) -> bool {
+    let ((pa, (wa, ha)), (pb, (wb, hb))) = (a, b);
+    pa.sheet == pb.sheet
+      && pa.x < pb.x + wb
+      && pb.x < pa.x + wa
+      && pa.y < pb.y + hb
+      && pb.y < pa.y + ha
+  }
+
+  #[test]
+  fn packs_without_overlap() {
+    let sizes = vec![(32, 32), (32, 64), (16, 8), (64, 32), (32, 32), (10, 50)];
+    let packing = pack(&sizes, 100, 1);
+    for (i, (place, size)) in packing.places.iter().zip(&sizes).enumerate() {
+      let (w, h) = packing.sheets[place.sheet];
+      assert!(place.x + size.0 <= w && place.y + size.1 <= h);
+      for (other, other_size) in packing.places.iter().zip(&sizes).skip(i + 1) {
+        assert!(!overlaps((*place, *size), (*other, *other_size)));
+      }
+    }
+  }
+
+  #[test]
+  fn starts_new_sheets_when_full() {
+    let packing = pack(&[(32, 32); 5], 64, 0);
+    assert_eq!(packing.sheets, vec![(64, 64), (32, 32)]);
+    assert_eq!(
+      packing.places[4],
+      Place {
+        sheet: 1,
+        x: 0,
+        y: 0
+      }
+    );
+
+    let oversized = pack(&[(200, 10), (10, 10)], 64, 0);
+    assert_eq!(oversized.sheets.len(), 2);
+  }
+}
@@ -0,0 +1,527 @@
+use enum_map::{Enum, EnumMap};
+use macroquad::prelude::{
+  get_last_key_pressed, is_key_down, is_key_pressed, is_key_released, is_mouse_button_down,
+  is_mouse_button_pressed, is_mouse_button_released, KeyCode, MouseButton,
+};
+use std::{
+  collections::BTreeMap,
+  fs::{create_dir_all, read_to_string, write},
+  path::Path,
+};
+
+use crate::{
+  gamepad::{self, Button},
+  save::data_path,
+};
+
+static BINDINGS_FILE: &str = "bindings.toml";
+
+/** Everything the player can do with a key or button. Game code asks about these, never keys. */
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Enum)]
+pub enum Action {
+  SelectBlockerUp,
+  SelectBlockerDown,
+  SelectLava,
+  SelectGeyser,
+  SelectCollector,
+  Deselect,
+  /** Presses a tower button, releases over the level to build. */
+  Place,
+  /** Picks up the tower under the cursor. Released after a drag it does nothing. */
+  Remove,
+  /** Upgrades the selected tower. */
+  Upgrade,
+  SpeedUp,
+  SpeedDown,
+  Spawn,
+  PanLeft,
+  PanRight,
+  PanUp,
+  PanDown,
+  /** Held while moving the mouse to drag the level around. */
+  PanDrag,
+  Pause,
+  Quit,
+  SaveReplay,
+  Confirm,
+  Back,
+  /** Starts the level at that place on the level select screen. */
+  PickLevel1,
+  PickLevel2,
+  PickLevel3,
+  PickLevel4,
+  PickLevel5,
+  PickLevel6,
+  PickLevel7,
+  PickLevel8,
+  PickLevel9,
+}
+
+/** The level picking actions in level select order. */
+pub const PICK_LEVEL: [Action; 9] = [
+  Action::PickLevel1,
+  Action::PickLevel2,
+  Action::PickLevel3,
+  Action::PickLevel4,
+  Action::PickLevel5,
+  Action::PickLevel6,
+  Action::PickLevel7,
+  Action::PickLevel8,
+  Action::PickLevel9,
+];
+
+impl Action {
+  pub fn all() -> impl Iterator<Item = Action> {
+    (0..Action::LENGTH).map(Action::from_usize)
+  }
+
+  /** Key in the bindings file. */
+  pub fn name(&self) -> &'static str {
+    match self {
+      Action::SelectBlockerUp => "select_blocker_up",
+      Action::SelectBlockerDown => "select_blocker_down",
+      Action::SelectLava => "select_lava",
+      Action::SelectGeyser => "select_geyser",
+      Action::SelectCollector => "select_collector",
+      Action::Deselect => "deselect",
+      Action::Place => "place",
+      Action::Remove => "remove",
+      Action::Upgrade => "upgrade",
+      Action::SpeedUp => "speed_up",
+      Action::SpeedDown => "speed_down",
+      Action::Spawn => "spawn",
+      Action::PanLeft => "pan_left",
+      Action::PanRight => "pan_right",
+      Action::PanUp => "pan_up",
+      Action::PanDown => "pan_down",
+      Action::PanDrag => "pan_drag",
+      Action::Pause => "pause",
+      Action::Quit => "quit",
+      Action::SaveReplay => "save_replay",
+      Action::Confirm => "confirm",
+      Action::Back => "back",
+      Action::PickLevel1 => "pick_level_1",
+      Action::PickLevel2 => "pick_level_2",
+      Action::PickLevel3 => "pick_level_3",
+      Action::PickLevel4 => "pick_level_4",
+      Action::PickLevel5 => "pick_level_5",
+      Action::PickLevel6 => "pick_level_6",
+      Action::PickLevel7 => "pick_level_7",
+      Action::PickLevel8 => "pick_level_8",
+      Action::PickLevel9 => "pick_level_9",
+    }
+  }
+  pub fn from_name(name: &str) -> Option<Action> {
+    Action::all().find(|a| a.name() == name)
+  }
+
+  /** Shown on the controls screen. */
+  pub fn label(&self) -> &'static str {
+    match self {
+      Action::SelectBlockerUp => "Select blocker up",
+      Action::SelectBlockerDown => "Select blocker down",
+      Action::SelectLava => "Select lava",
+      Action::SelectGeyser => "Select geyser",
+      Action::SelectCollector => "Select collector",
+      Action::Deselect => "Deselect",
+      Action::Place => "Build",
+      Action::Remove => "Pick up tower",
+      Action::Upgrade => "Upgrade tower",
+      Action::SpeedUp => "Speed up",
+      Action::SpeedDown => "Slow down",
+      Action::Spawn => "Spawn enemy",
+      Action::PanLeft => "Pan left",
+      Action::PanRight => "Pan right",
+      Action::PanUp => "Pan up",
+      Action::PanDown => "Pan down",
+      Action::PanDrag => "Drag view",
+      Action::Pause => "Pause",
+      Action::Quit => "Quit level",
+      Action::SaveReplay => "Save replay",
+      Action::Confirm => "Confirm",
+      Action::Back => "Back",
+      Action::PickLevel1 => "Pick level 1",
+      Action::PickLevel2 => "Pick level 2",
+      Action::PickLevel3 => "Pick level 3",
+      Action::PickLevel4 => "Pick level 4",
+      Action::PickLevel5 => "Pick level 5",
+      Action::PickLevel6 => "Pick level 6",
+      Action::PickLevel7 => "Pick level 7",
+      Action::PickLevel8 => "Pick level 8",
+      Action::PickLevel9 => "Pick level 9",
+    }
+  }
+}
+
+/** One key, mouse button or gamepad button. */
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Binding {
+  Key(KeyCode),
+  Mouse(MouseButton),
+  Pad(Button),
+}
+
+/** Keys that can be named in the bindings file, by their `KeyCode` name. */
+const KEYS: &[KeyCode] = &[
+  KeyCode::Space,
+  KeyCode::Apostrophe,
+  KeyCode::Comma,
+  KeyCode::Minus,
+  KeyCode::Period,
+  KeyCode::Slash,
+  KeyCode::Key0,
+  KeyCode::Key1,
+  KeyCode::Key2,
+  KeyCode::Key3,
+  KeyCode::Key4,
+  KeyCode::Key5,
+  KeyCode::Key6,
+  KeyCode::Key7,
+  KeyCode::Key8,
+  KeyCode::Key9,
+  KeyCode::Semicolon,
+  KeyCode::Equal,
+  KeyCode::A,
+  KeyCode::B,
+  KeyCode::C,
+  KeyCode::D,
+  KeyCode::E,
+  KeyCode::F,
+  KeyCode::G,
+  KeyCode::H,
+  KeyCode::I,
+  KeyCode::J,
+  KeyCode::K,
+  KeyCode::L,
+  KeyCode::M,
+  KeyCode::N,
+  KeyCode::O,
+  KeyCode::P,
+  KeyCode::Q,
+  KeyCode::R,
+  KeyCode::S,
+  KeyCode::T,
+  KeyCode::U,
+  KeyCode::V,
+  KeyCode::W,
+  KeyCode::X,
+  KeyCode::Y,
+  KeyCode::Z,
+  KeyCode::LeftBracket,
+  KeyCode::Backslash,
+  KeyCode::RightBracket,
+  KeyCode::GraveAccent,
+  KeyCode::Escape,
+  KeyCode::Enter,
+  KeyCode::Tab,
+  KeyCode::Backspace,
+  KeyCode::Insert,
+  KeyCode::Delete,
+  KeyCode::Right,
+  KeyCode::Left,
+  KeyCode::Down,
+  KeyCode::Up,
+  KeyCode::PageUp,
+  KeyCode::PageDown,
+  KeyCode::Home,
+  KeyCode::End,
+  KeyCode::Pause,
+  KeyCode::F1,
+  KeyCode::F2,
+  KeyCode::F3,
+  KeyCode::F4,
+  KeyCode::F5,
+  KeyCode::F6,
+  KeyCode::F7,
+  KeyCode::F8,
+  KeyCode::F9,
+  KeyCode::F10,
+  KeyCode::F11,
+  KeyCode::F12,
+  KeyCode::Kp0,
+  KeyCode::Kp1,
+  KeyCode::Kp2,
+  KeyCode::Kp3,
+  KeyCode::Kp4,
+  KeyCode::Kp5,
+  KeyCode::Kp6,
+  KeyCode::Kp7,
+  KeyCode::Kp8,
+  KeyCode::Kp9,
+  KeyCode::KpDecimal,
+  KeyCode::KpDivide,
+  KeyCode::KpMultiply,
+  KeyCode::KpSubtract,
+  KeyCode::KpAdd,
+  KeyCode::KpEnter,
+  KeyCode::KpEqual,
+  KeyCode::LeftShift,
+  KeyCode::LeftControl,
+  KeyCode::LeftAlt,
+  KeyCode::RightShift,
+  KeyCode::RightControl,
+  KeyCode::RightAlt,
+];
+const MOUSE_BUTTONS: &[MouseButton] = &[MouseButton::Left, MouseButton::Right, MouseButton::Middle];
+/** Gamepad buttons that can be named in the bindings file, by their gilrs `Button` name. */
+const PAD_BUTTONS: &[Button] = &[
+  Button::South,
+  Button::East,
+  Button::North,
+  Button::West,
+  Button::C,
+  Button::Z,
+  Button::LeftTrigger,
+  Button::LeftTrigger2,
+  Button::RightTrigger,
+  Button::RightTrigger2,
+  Button::Select,
+  Button::Start,
+  Button::Mode,
+  Button::LeftThumb,
+  Button::RightThumb,
+  Button::DPadUp,
+  Button::DPadDown,
+  Button::DPadLeft,
+  Button::DPadRight,
+];
+
+impl Binding {
+  /**
+   * `KeyCode` names for keys, `MouseLeft`, `MouseRight` and `MouseMiddle` for mouse buttons and
+   * `Pad` followed by the gilrs `Button` name for gamepad buttons, e.g. `PadSouth`.
+   */
+  pub fn name(&self) -> String {
+    match self {
+      Binding::Key(key) => format!("{:?}", key),
+      Binding::Mouse(button) => format!("Mouse{:?}", button),
+      Binding::Pad(button) => format!("Pad{:?}", button),
+    }
+  }
+  pub fn parse(name: &str) -> Option<Binding> {
+    let key = KEYS.iter().find(|k| format!("{:?}", k) == name);
+    let button = MOUSE_BUTTONS
+      .iter()
+      .find(|b| format!("Mouse{:?}", b) == name);
+    let pad = PAD_BUTTONS.iter().find(|b| format!("Pad{:?}", b) == name);
+    key
+      .map(|k| Binding::Key(*k))
+      .or_else(|| button.map(|b| Binding::Mouse(*b)))
+      .or_else(|| pad.map(|b| Binding::Pad(*b)))
+  }
+  /** The key, mouse button or gamepad button that went down this frame, if any. */
+  pub fn last_pressed() -> Option<Binding> {
+    get_last_key_pressed()
+      .map(Binding::Key)
+      .or_else(|| {
+        let button = MOUSE_BUTTONS.iter().find(|b| is_mouse_button_pressed(**b));
+        button.map(|b| Binding::Mouse(*b))
+      })
+      .or_else(|| gamepad::get_last_button_pressed().map(Binding::Pad))
+  }
+
+  fn is_down(&self) -> bool {
+    match self {
+      Binding::Key(key) => is_key_down(*key),
+      Binding::Mouse(button) => is_mouse_button_down(*button),
+      Binding::Pad(button) => gamepad::is_button_down(*button),
+    }
+  }
+  fn is_pressed(&self) -> bool {
+    match self {
+      Binding::Key(key) => is_key_pressed(*key),
+      Binding::Mouse(button) => is_mouse_button_pressed(*button),
+      Binding::Pad(button) => gamepad::is_button_pressed(*button),
+    }
+  }
+  fn is_released(&self) -> bool {
+    match self {
+      Binding::Key(key) => is_key_released(*key),
+      Binding::Mouse(button) => is_mouse_button_released(*button),
+      Binding::Pad(button) => gamepad::is_button_released(*button),
+    }
+  }
+}
+
+/**
+ * The bindings of every action. Stored in the user data directory as `bindings.toml`, one line
+ * per changed action:
+ * ```toml
+ * speed_up = ["KpAdd", "Enter", "PadRightTrigger"]
+ * pan_drag = ["MouseRight"]
+ * ```
+ * Actions missing from the file keep their default bindings.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bindings {
+  map: EnumMap<Action, Vec<Binding>>,
+}
+
+impl Bindings {
+  pub fn defaults() -> Bindings {
+    use Binding::{Key, Mouse, Pad};
+    Bindings {
+      map: enum_map! {
+        Action::SelectBlockerUp => vec![Key(KeyCode::Key1)],
+        Action::SelectBlockerDown => vec![Key(KeyCode::Key2)],
+        Action::SelectLava => vec![Key(KeyCode::Key3)],
+        Action::SelectCollector => vec![Key(KeyCode::Key4)],
+        Action::SelectGeyser => vec![Key(KeyCode::Key5)],
+        Action::Deselect => vec![Key(KeyCode::Escape), Pad(Button::East)],
+        Action::Place => vec![Mouse(MouseButton::Left)],
+        Action::Remove => vec![Mouse(MouseButton::Right)],
+        Action::Upgrade => vec![Key(KeyCode::U), Pad(Button::North)],
+        Action::SpeedUp => vec![
+          Key(KeyCode::KpAdd),
+          Key(KeyCode::Enter),
+          Pad(Button::RightTrigger),
+        ],
+        Action::SpeedDown => vec![
+          Key(KeyCode::KpSubtract),
+          Key(KeyCode::Backspace),
+          Pad(Button::LeftTrigger),
+        ],
+        Action::Spawn => vec![Key(KeyCode::Space)],
+        Action::PanLeft => vec![Key(KeyCode::A), Key(KeyCode::Left), Pad(Button::DPadLeft)],
+        Action::PanRight => vec![Key(KeyCode::D), Key(KeyCode::Right), Pad(Button::DPadRight)],
+        Action::PanUp => vec![Key(KeyCode::W), Key(KeyCode::Up), Pad(Button::DPadUp)],
+        Action::PanDown => vec![Key(KeyCode::S), Key(KeyCode::Down), Pad(Button::DPadDown)],
+        Action::PanDrag => vec![Mouse(MouseButton::Right)],
+        Action::Pause => vec![Key(KeyCode::P), Pad(Button::Start)],
+        Action::Quit => vec![Key(KeyCode::Q), Pad(Button::Select)],
+        Action::SaveReplay => vec![Key(KeyCode::F5)],
+        Action::Confirm => vec![Key(KeyCode::Enter), Pad(Button::South)],
+        Action::Back => vec![Key(KeyCode::Escape), Pad(Button::East)],
+        Action::PickLevel1 => vec![Key(KeyCode::Key1)],
+        Action::PickLevel2 => vec![Key(KeyCode::Key2)],
+        Action::PickLevel3 => vec![Key(KeyCode::Key3)],
+        Action::PickLevel4 => vec![Key(KeyCode::Key4)],
+        Action::PickLevel5 => vec![Key(KeyCode::Key5)],
+        Action::PickLevel6 => vec![Key(KeyCode::Key6)],
+        Action::PickLevel7 => vec![Key(KeyCode::Key7)],
+        Action::PickLevel8 => vec![Key(KeyCode::Key8)],
+        Action::PickLevel9 => vec![Key(KeyCode::Key9)],
+      },
+    }
+  }
+
+  /**
+   * Loads the player's bindings, or the defaults if there are none. Fails on a bindings file that
+   * is broken, which the caller can replace with the defaults.
+   */
+  pub fn load() -> Result<Bindings, String> {
+    let path = match data_path(BINDINGS_FILE) {
+      Some(path) if path.exists() => path,
+      _ => return Ok(Bindings::defaults()),
+    };
+    read_to_string(&path)
+      .map_err(|e| e.to_string())
+      .and_then(|text| Bindings::parse(&text))
+      .map_err(|e| format!("{}: {}", path.display(), e))
+  }
+  pub fn save(&self) -> Result<(), String> {
+    match data_path(BINDINGS_FILE) {
+      Some(path) => self
+        .save_to(&path)
+        .map_err(|e| format!("{}: {}", path.display(), e)),
+      None => Ok(()),
+    }
+  }
+  pub fn save_to(&self, path: &Path) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+      create_dir_all(dir)?;
+    }
+    write(path, self.to_toml())
+  }
+
+  pub fn parse(text: &str) -> Result<Bindings, String> {
+    let table: BTreeMap<String, Vec<String>> = toml::from_str(text).map_err(|e| e.to_string())?;
+    let mut bindings = Bindings::defaults();
+    for (name, binding_names) in table {
+      let action = Action::from_name(&name).ok_or_else(|| format!("unknown action {}", name))?;
+      bindings.map[action] = binding_names
+        .iter()
+        .map(|b| Binding::parse(b).ok_or_else(|| format!("{}: unknown key {}", name, b)))
+        .collect::<Result<_, _>>()?;
+    }
+    Ok(bindings)
+  }
+  /** Only the actions that differ from the defaults. */
+  pub fn to_toml(&self) -> String {
+    let defaults = Bindings::defaults();
+    let table: BTreeMap<&str, Vec<String>> = Action::all()
+      .filter(|a| self.map[*a] != defaults.map[*a])
+      .map(|a| (a.name(), self.map[a].iter().map(Binding::name).collect()))
+      .collect();
+    toml::to_string(&table).unwrap_or_default()
+  }
+
+  pub fn get(&self, action: Action) -> &[Binding] {
+    &self.map[action]
+  }
+  /** Makes `binding` the only binding of `action`. */
+  pub fn rebind(&mut self, action: Action, binding: Binding) {
+    self.map[action] = vec![binding];
+  }
+  /** The bindings of `action` for display, e.g. "KpAdd / Enter". */
+  pub fn describe(&self, action: Action) -> String {
+    let names: Vec<String> = self.map[action].iter().map(Binding::name).collect();
+    if names.is_empty() {
+      "unbound".to_owned()
+    } else {
+      names.join(" / ")
+    }
+  }
+
+  pub fn is_down(&self, action: Action) -> bool {
+    self.map[action].iter().any(Binding::is_down)
+  }
+  /** Went down this frame. */
+  pub fn is_pressed(&self, action: Action) -> bool {
+    self.map[action].iter().any(Binding::is_pressed)
+  }
+  /** Went up this frame. */
+  pub fn is_released(&self, action: Action) -> bool {
+    self.map[action].iter().any(Binding::is_released)
+  }
+}
+
+impl Default for Bindings {
+  fn default() -> Self {
+    Bindings::defaults()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn names_round_trip() {
+    for action in Action::all() {
+      assert_eq!(Action::from_name(action.name()), Some(action));
+    }
+    for binding in [
+      Binding::Key(KeyCode::KpAdd),
+      Binding::Mouse(MouseButton::Middle),
+      Binding::Pad(Button::DPadLeft),
+    ] {
+      assert_eq!(Binding::parse(&binding.name()), Some(binding));
+    }
+  }
+
+  #[test]
+  fn file_overrides_defaults() {
+    let mut bindings = Bindings::defaults();
+    bindings.rebind(Action::Spawn, Binding::Key(KeyCode::G));
+    let text = bindings.to_toml();
+    assert_eq!(text.trim(), "spawn = [\"G\"]");
+    assert_eq!(Bindings::parse(&text), Ok(bindings));
+  }
+
+  #[test]
+  fn rejects_unknown_names() {
+    assert!(Bindings::parse("jump = [\"Space\"]").is_err());
+    assert!(Bindings::parse("spawn = [\"Hyper\"]").is_err());
+  }
+}
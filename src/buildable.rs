@@ -1,56 +1,357 @@
+use enum_dispatch::enum_dispatch;
 use macroquad::{
-  prelude::{vec2, PINK, RED, WHITE},
-  texture::{draw_texture_ex, DrawTextureParams, Texture2D},
-  time::get_frame_time,
+  prelude::{vec2, Color, PINK, RED, WHITE},
+  texture::DrawTextureParams,
 };
+use std::fmt::Display;
 
 use crate::{
   deb::DEBUG,
-  rect::Rect,
-  tower::{Dir, FrameDrawing, TowerType},
-  wrld::World,
+  effects::EffectKind,
+  emath::grid_pos_to_pos,
+  loading::Textures,
+  rect::{Collidable, Rect},
+  tower::{offset_rect, TowerDef, TowerTable, TowerTier, TowerType},
 };
 
+/** Tint of a full collector. */
+const FULL_COLOR: Color = Color::new(0.5, 0.5, 0.5, 1.0);
+
+/** A built or painted tower. Which type it is follows from its kind, see `Tower::new`. */
+#[enum_dispatch(Buildable)]
+pub enum Tower {
+  Blocker,
+  LavaTower,
+  Collector,
+}
+
+/** What every tower has, whatever its kind. */
+pub struct TowerBase {
+  grid_pos: (usize, usize),
+  kind: TowerType,
+  /** Offset and size; in pixels units. */
+  draw_pos: Rect,
+  rect: Rect,
+  solid: bool,
+  /** Seconds since the tower was built, drives its animation. */
+  age: f32,
+  /** Index into the kind's tiers, 0 when built. */
+  tier: usize,
+  stats: TowerTier,
+  /** Painted into the level rather than built by the player; free of supply. */
+  pub placed: bool,
+}
+impl TowerBase {
+  fn new(kind: TowerType, grid_pos: (usize, usize), table: &TowerTable) -> TowerBase {
+    TowerBase {
+      grid_pos,
+      kind,
+      draw_pos: Rect::new(0, 0, 0, 0),
+      rect: Rect::new(0, 0, 0, 0),
+      solid: table.def(kind).solid,
+      age: 0.0,
+      tier: 0,
+      stats: table.get(kind, 0).clone(),
+      placed: false,
+    }
+  }
+  /** Top left corner of the build tile, in level pixels. */
+  fn pos(&self) -> (usize, usize) {
+    grid_pos_to_pos(&self.grid_pos)
+  }
+  /** The tier's tint, white if it has none. */
+  fn tint(&self) -> Color {
+    let [r, g, b] = self.stats.tint.unwrap_or([255, 255, 255]);
+    Color::from_rgba(r, g, b, 255)
+  }
+  fn apply_stats(&mut self) {
+    let pos = self.pos();
+    self.rect = offset_rect(&self.stats.hitbox.unwrap_or_default(), pos);
+    self.draw_pos = offset_rect(&self.stats.draw.unwrap_or_default(), pos);
+  }
+}
+
+#[enum_dispatch]
 pub trait Buildable {
-  fn get_atlas(&mut self) -> Option<&mut FrameDrawing>;
-  fn get_default_texture(&self) -> Texture2D;
-  fn get_draw_pos(&self) -> &Rect;
-  fn get_grid_pos(&self) -> &(usize, usize);
-  fn get_hitbox(&self) -> &Rect;
-  fn get_kind(&self) -> &TowerType;
-  fn get_direction(&self) -> &Dir;
-
-  fn get_texture(&mut self) -> Texture2D {
-    if let Some(atlas) = self.get_atlas() {
-      atlas.timer -= get_frame_time();
-      if atlas.timer <= 0.0 {
-        atlas.reset_timer();
-        atlas.frame = (atlas.frame + 1) % atlas.count;
-      }
-      return atlas.frames[atlas.frame];
-    }
-    self.get_default_texture()
-  }
-  fn draw(&mut self, wrld: &World) {
-    let dp = self.get_draw_pos();
-    let tl = dp.tl();
-    let width = dp.width() as f32 * wrld.zoom;
-    let height = dp.height() as f32 * wrld.zoom;
-    draw_texture_ex(
-      self.get_texture(),
-      (tl.0 as f32 + wrld.scroll_pos.x) * wrld.zoom,
-      (tl.1 as f32 + wrld.scroll_pos.y) * wrld.zoom,
-      WHITE,
+  fn base(&self) -> &TowerBase;
+  fn base_mut(&mut self) -> &mut TowerBase;
+
+  /** Ages the tower. Returns the effect it spawns this tick, if any. */
+  fn update(&mut self, dt: f32) -> Option<(EffectKind, (usize, usize))> {
+    self.base_mut().age += dt;
+    None
+  }
+  /** Whether an enemy at `rect` bumps into the tower. */
+  fn blocks(&self, rect: &Rect) -> bool {
+    let base = self.base();
+    base.solid && base.rect.intersecting(rect)
+  }
+  /** Whether the tower takes an enemy at `rect`, counting it if so. */
+  fn catch(&mut self, _rect: &Rect) -> bool {
+    false
+  }
+  /** Enemies caught so far. */
+  fn get_count(&self) -> usize {
+    0
+  }
+  fn is_full(&self) -> bool {
+    false
+  }
+  /** Sets size, reach and timing from the tier's stats. */
+  fn apply_stats(&mut self) {
+    self.base_mut().apply_stats();
+  }
+  /** Moves the tower to tier `tier` of its kind in `table`. */
+  fn set_tier(&mut self, tier: usize, table: &TowerTable) {
+    let base = self.base_mut();
+    base.tier = tier;
+    base.stats = table.get(base.kind, tier).clone();
+    self.apply_stats();
+  }
+
+  fn get_sprite(&self) -> &str {
+    self.base().stats.sprite.as_deref().unwrap_or_default()
+  }
+  fn get_kind(&self) -> TowerType {
+    self.base().kind
+  }
+  fn get_grid_pos(&self) -> (usize, usize) {
+    self.base().grid_pos
+  }
+  fn get_tier(&self) -> usize {
+    self.base().tier
+  }
+  fn is_placed(&self) -> bool {
+    self.base().placed
+  }
+  /** Where the sprite goes, in level pixels. */
+  fn get_draw_rect(&self) -> &Rect {
+    &self.base().draw_pos
+  }
+  fn get_color(&self) -> Color {
+    WHITE
+  }
+
+  /** Draws in level pixels, under the camera, in `get_color` tinted by the tier. */
+  fn draw(&self, texs: &Textures) {
+    let base = self.base();
+    let tl = base.draw_pos.tl();
+    let width = base.draw_pos.width() as f32;
+    let height = base.draw_pos.height() as f32;
+    let (color, tint) = (self.get_color(), base.tint());
+    texs.frame(self.get_sprite(), base.age).draw(
+      tl.0 as f32,
+      tl.1 as f32,
+      Color::new(
+        color.r * tint.r,
+        color.g * tint.g,
+        color.b * tint.b,
+        color.a,
+      ),
       DrawTextureParams {
         dest_size: Some(vec2(width, height)),
         ..Default::default()
       },
     );
     if DEBUG.draw_rects {
-      match self.get_kind() {
-        TowerType::BlockerDown | TowerType::BlockerUp => self.get_hitbox().debug_draw(RED),
-        _ => self.get_hitbox().debug_draw(PINK),
-      }
+      base.rect.debug_draw(if base.solid { RED } else { PINK });
+    }
+  }
+}
+
+impl Tower {
+  /** A new tower of `kind` at the first tier of its definition in `table`. */
+  pub fn new(kind: TowerType, grid_pos: (usize, usize), table: &TowerTable) -> Tower {
+    let base = TowerBase::new(kind, grid_pos, table);
+    let mut tower: Tower = match kind {
+      TowerType::BlockerDown | TowerType::BlockerUp => Blocker { base }.into(),
+      TowerType::Lava | TowerType::Geyser => LavaTower::new(base, table.def(kind)).into(),
+      TowerType::Collector => Collector::new(base, table.def(kind)).into(),
+    };
+    tower.apply_stats();
+    tower
+  }
+}
+
+impl Display for Tower {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{:?}@{:?}", self.get_kind(), self.get_grid_pos())
+  }
+}
+
+impl Collidable for Tower {
+  fn collide(&self, other: &impl Collidable) -> bool {
+    self.blocks(other.get_hitbox())
+  }
+  fn get_hitbox(&self) -> &Rect {
+    &self.base().rect
+  }
+}
+
+//
+//
+//
+//
+//
+//
+//
+// BLOCKER
+//
+/** Stands in the way; enemies walk into it and turn around. */
+pub struct Blocker {
+  base: TowerBase,
+}
+impl Buildable for Blocker {
+  fn base(&self) -> &TowerBase {
+    &self.base
+  }
+  fn base_mut(&mut self) -> &mut TowerBase {
+    &mut self.base
+  }
+}
+
+//
+//
+//
+//
+//
+//
+//
+// LAVATOWER
+//
+/** Drops its effect on a tile next to it every `interval` seconds. */
+pub struct LavaTower {
+  base: TowerBase,
+  effect: EffectKind,
+  /** Top left corner of the tile the effect spawns on, in level pixels. */
+  effect_pos: (usize, usize),
+  /** Seconds until the next effect. */
+  timer: f32,
+  interval: f32,
+}
+impl LavaTower {
+  fn new(base: TowerBase, def: &TowerDef) -> LavaTower {
+    let at = |pos: usize, offset: isize| (pos as isize + offset).max(0) as usize;
+    LavaTower {
+      effect: def.effect.unwrap_or(EffectKind::LavaDrop),
+      effect_pos: grid_pos_to_pos(&(
+        at(base.grid_pos.0, def.effect_tile[0]),
+        at(base.grid_pos.1, def.effect_tile[1]),
+      )),
+      base,
+      timer: 0.0,
+      interval: 0.0,
+    }
+  }
+}
+impl Buildable for LavaTower {
+  fn base(&self) -> &TowerBase {
+    &self.base
+  }
+  fn base_mut(&mut self) -> &mut TowerBase {
+    &mut self.base
+  }
+  fn update(&mut self, dt: f32) -> Option<(EffectKind, (usize, usize))> {
+    self.base.age += dt;
+    self.timer -= dt;
+    if self.timer <= 0.0 {
+      self.timer = self.interval;
+      return Some((self.effect, self.effect_pos));
+    }
+    None
+  }
+  /** A faster tier also shortens the wait for the next effect. */
+  fn apply_stats(&mut self) {
+    self.base.apply_stats();
+    self.interval = self.base.stats.interval.unwrap_or_default();
+    self.timer = self.timer.min(self.interval);
+  }
+}
+
+//
+//
+//
+//
+//
+//
+//
+// COLLECTOR
+//
+/** Takes the enemies touching its trigger until it holds its capacity. */
+pub struct Collector {
+  base: TowerBase,
+  trigger: Rect,
+  capacity: Option<usize>,
+  count: usize,
+}
+impl Collector {
+  fn new(base: TowerBase, def: &TowerDef) -> Collector {
+    Collector {
+      base,
+      trigger: Rect::new(0, 0, 0, 0),
+      capacity: def.capacity,
+      count: 0,
+    }
+  }
+}
+impl Buildable for Collector {
+  fn base(&self) -> &TowerBase {
+    &self.base
+  }
+  fn base_mut(&mut self) -> &mut TowerBase {
+    &mut self.base
+  }
+  fn catch(&mut self, rect: &Rect) -> bool {
+    if self.is_full() || !self.trigger.intersecting(rect) {
+      return false;
+    }
+    self.count += 1;
+    true
+  }
+  fn get_count(&self) -> usize {
+    self.count
+  }
+  fn is_full(&self) -> bool {
+    match self.capacity {
+      Some(capacity) => self.count >= capacity,
+      None => false,
     }
   }
+  fn apply_stats(&mut self) {
+    self.base.apply_stats();
+    let trigger = self.base.stats.trigger.unwrap_or_default();
+    self.trigger = offset_rect(&trigger, self.base.pos());
+  }
+  fn get_color(&self) -> Color {
+    if self.is_full() {
+      FULL_COLOR
+    } else {
+      WHITE
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::loading::read_tower_table;
+
+  #[test]
+  fn lava_towers_drop_every_interval() {
+    let table = read_tower_table().unwrap();
+    let mut tower = Tower::new(TowerType::Lava, (6, 5), &table);
+    let interval = table.get(TowerType::Lava, 0).interval.unwrap();
+    let below = grid_pos_to_pos(&(6, 6));
+    assert_eq!(tower.update(0.1), Some((EffectKind::LavaDrop, below)));
+    assert_eq!(tower.update(interval - 0.2), None);
+    assert!(tower.update(0.3).is_some());
+
+    let mut geyser = Tower::new(TowerType::Geyser, (6, 5), &table);
+    let above = grid_pos_to_pos(&(6, 4));
+    assert_eq!(geyser.update(0.1), Some((EffectKind::LavaSplash, above)));
+
+    //Blockers never spawn anything.
+    let mut blocker = Tower::new(TowerType::BlockerDown, (2, 6), &table);
+    assert_eq!(blocker.update(interval * 2.0), None);
+  }
 }
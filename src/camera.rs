@@ -0,0 +1,125 @@
+use macroquad::{
+  camera::Camera2D,
+  math::{self, vec2, Vec2},
+};
+
+use crate::rect::Rect;
+
+pub const MIN_ZOOM: f32 = 0.5;
+pub const MAX_ZOOM: f32 = 3.0;
+/** Level pixels that stay on screen however far the camera is moved. */
+const EDGE_MARGIN: f32 = 64.0;
+
+/**
+ * Maps level pixels to screen pixels. `pos` is the level point shown at the top left corner of
+ * the screen, `zoom` the screen pixels per level pixel.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Camera {
+  pub pos: Vec2,
+  pub zoom: f32,
+  /** Window size in screen pixels, kept up to date by the view. */
+  pub screen: Vec2,
+}
+
+impl Camera {
+  pub fn new(pos: Vec2, screen: Vec2) -> Camera {
+    Camera {
+      pos,
+      zoom: 1.0,
+      screen,
+    }
+  }
+
+  pub fn world_to_screen(&self, point: Vec2) -> Vec2 {
+    (point - self.pos) * self.zoom
+  }
+  pub fn screen_to_world(&self, point: Vec2) -> Vec2 {
+    point / self.zoom + self.pos
+  }
+
+  /** Moves the level along with a drag of `delta` screen pixels. */
+  pub fn drag(&mut self, delta: Vec2) {
+    self.pos -= delta / self.zoom;
+  }
+  /** Changes the zoom while the level point under `screen_point` stays where it is. */
+  pub fn zoom_at(&mut self, screen_point: Vec2, zoom: f32) {
+    let anchor = self.screen_to_world(screen_point);
+    self.zoom = zoom.clamp(MIN_ZOOM, MAX_ZOOM);
+    self.pos = anchor - screen_point / self.zoom;
+  }
+  /** Keeps at least a margin of a level `level_size` pixels large on screen. */
+  pub fn clamp(&mut self, level_size: Vec2) {
+    let visible = self.screen / self.zoom;
+    let min = Vec2::splat(EDGE_MARGIN) - visible;
+    let max = (level_size - Vec2::splat(EDGE_MARGIN)).max(min);
+    self.pos = self.pos.clamp(min, max);
+  }
+
+  /** Whether any part of `rect`, in level pixels, is on screen. */
+  pub fn is_visible(&self, rect: &Rect) -> bool {
+    let end = self.pos + self.screen / self.zoom;
+    (rect.right as f32) > self.pos.x
+      && (rect.left as f32) < end.x
+      && (rect.bottom as f32) > self.pos.y
+      && (rect.top as f32) < end.y
+  }
+
+  /** A macroquad camera that draws level pixels to their place on screen. */
+  pub fn camera2d(&self) -> Camera2D {
+    let size = self.screen / self.zoom;
+    Camera2D::from_display_rect(math::Rect::new(self.pos.x, self.pos.y, size.x, size.y))
+  }
+}
+
+impl Default for Camera {
+  fn default() -> Self {
+    Camera::new(vec2(0.0, 0.0), vec2(1600.0, 900.0))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn close(a: Vec2, b: Vec2) -> bool {
+    (a - b).length() < 0.001
+  }
+
+  #[test]
+  fn transforms_round_trip() {
+    let mut camera = Camera::new(vec2(-100.0, 40.0), vec2(800.0, 600.0));
+    camera.zoom = 2.0;
+    let world = vec2(30.0, 70.0);
+    let screen = camera.world_to_screen(world);
+    assert!(close(screen, vec2(260.0, 60.0)));
+    assert!(close(camera.screen_to_world(screen), world));
+  }
+
+  #[test]
+  fn zooms_around_the_cursor() {
+    let mut camera = Camera::default();
+    let cursor = vec2(300.0, 200.0);
+    let under_cursor = camera.screen_to_world(cursor);
+    camera.zoom_at(cursor, 2.5);
+    assert_eq!(camera.zoom, 2.5);
+    assert!(close(camera.screen_to_world(cursor), under_cursor));
+
+    camera.zoom_at(cursor, 100.0);
+    assert_eq!(camera.zoom, MAX_ZOOM);
+  }
+
+  #[test]
+  fn clamps_to_the_level() {
+    let mut camera = Camera::new(vec2(5000.0, -5000.0), vec2(800.0, 600.0));
+    camera.clamp(vec2(576.0, 928.0));
+    assert_eq!(camera.pos, vec2(576.0 - EDGE_MARGIN, EDGE_MARGIN - 600.0));
+  }
+
+  #[test]
+  fn culls_off_screen_rects() {
+    let camera = Camera::new(vec2(0.0, 0.0), vec2(320.0, 320.0));
+    assert!(camera.is_visible(&Rect::new(300, 300, 340, 340)));
+    assert!(!camera.is_visible(&Rect::new(330, 0, 360, 32)));
+  }
+}
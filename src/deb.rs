@@ -1,7 +1,7 @@
 use lazy_static::lazy_static;
 use macroquad::{prelude::*, telemetry};
 
-use crate::wrld::World;
+use crate::{view::View, wrld::World};
 
 lazy_static! {
   pub static ref DEBUG: DebugSettings = DebugSettings {
@@ -62,7 +62,7 @@ impl Default for DebugPrintSettings {
   }
 }
 
-pub fn draw_debug_texts(deb_state: &DebugSettings, wrld: &World) {
+pub fn draw_debug_texts(deb_state: &DebugSettings, wrld: &World, view: &View) {
   let mut y = 10f32;
   if deb_state.draw_fps {
     let f = telemetry::frame();
@@ -84,9 +84,9 @@ pub fn draw_debug_texts(deb_state: &DebugSettings, wrld: &World) {
       y,
       format!(
         "mouse: ({}, {})  grid:{:?}",
-        wrld.mouse_pos.0,
-        wrld.mouse_pos.1,
-        wrld.get_mouse_grid(),
+        view.mouse_pos.0,
+        view.mouse_pos.1,
+        view.get_mouse_grid(),
       ),
     ));
     y += 15.0;
@@ -118,5 +118,5 @@ pub fn draw_debug_texts(deb_state: &DebugSettings, wrld: &World) {
 }
 
 pub fn print(s: DebugPrintSettings) {
-  draw_text(&s.prefix.unwrap_or(String::new()), s.x, s.y, s.fs, s.color);
+  draw_text(&s.prefix.unwrap_or_default(), s.x, s.y, s.fs, s.color);
 }
@@ -1,28 +1,48 @@
 use enum_dispatch::enum_dispatch;
 use macroquad::{
   prelude::{vec2, Vec2, RED, WHITE},
-  texture::{draw_texture_ex, DrawTextureParams, Texture2D},
+  texture::DrawTextureParams,
 };
+use serde::Deserialize;
 
 use crate::{
-  emath::{grid_pos_to_pos, pos_to_grid_pos},
+  deb::DEBUG,
+  enemy::Enemy,
   loading::Textures,
+  physics::{Aabb, Colliders},
   rect::{Collidable, Rect},
-  tower::FrameDrawing,
+  spatial::SpatialHash,
   wrld::World,
 };
 
+/** Seconds a splash stays on the ground. */
+const LAVA_SPLASH_TIME: f32 = 0.3;
+/** Hit points a lava drop takes from the enemies it lands on. */
+const LAVA_DROP_DAMAGE: f32 = 10.0;
+/** Hit points a splash takes every second from the enemies standing in it. */
+const LAVA_SPLASH_DPS: f32 = 25.0;
+
 #[enum_dispatch(Effect)]
 pub enum Effects {
   LavaDrop,
   LavaSplash,
 }
 
-#[derive(Debug, PartialEq, Clone, Copy)]
+#[derive(Deserialize, Debug, PartialEq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
 pub enum EffectKind {
   LavaDrop,
   LavaSplash,
 }
+/** What an effect does to the enemies it touches. */
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Damage {
+  /** Dealt once to everything the effect touches, which uses the effect up. */
+  Burst(f32),
+  /** Dealt every second an enemy overlaps the effect. */
+  PerSecond(f32),
+}
+
 pub struct EffectUpdateReturn {
   pub spawn: Option<(EffectKind, (usize, usize))>,
   pub keep: bool,
@@ -50,49 +70,43 @@ pub trait Effect {
   fn get_draw_pos(&self) -> Rect;
   fn get_pos(&self) -> &(usize, usize);
   fn get_rect(&self) -> &Rect;
-  fn get_atlas(&mut self) -> &mut Option<FrameDrawing>;
-  fn get_default_texture(&self) -> Texture2D;
+  fn get_sprite(&self) -> &'static str;
+  /** Seconds since the effect spawned, drives its animation. */
+  fn get_age(&self) -> f32;
   fn get_kind(&self) -> &EffectKind;
+  fn get_damage(&self) -> Damage;
   fn update(&mut self, wrld: &World) -> EffectUpdateReturn;
-
-  fn get_texture(&mut self) -> Texture2D {
-    if let Some(atlas) = self.get_atlas() {
-      return atlas.step_time();
-    }
-    self.get_default_texture()
+  /** Spawned in place of a burst that was used up on an enemy. */
+  fn on_hit(&self) -> Option<(EffectKind, (usize, usize))> {
+    None
   }
-  fn draw(&mut self, wrld: &World) {
+
+  /** Draws in level pixels, under the camera. */
+  fn draw(&self, texs: &Textures) {
     let dp = self.get_draw_pos();
     let tl = dp.tl();
-    let width = dp.width() as f32 * wrld.zoom;
-    let height = dp.height() as f32 * wrld.zoom;
-    draw_texture_ex(
-      self.get_texture(),
-      (tl.0 as f32 + wrld.scroll_pos.x) * wrld.zoom,
-      (tl.1 as f32 + wrld.scroll_pos.y) * wrld.zoom,
+    let width = dp.width() as f32;
+    let height = dp.height() as f32;
+    texs.frame(self.get_sprite(), self.get_age()).draw(
+      tl.0 as f32,
+      tl.1 as f32,
       WHITE,
       DrawTextureParams {
         dest_size: Some(vec2(width, height)),
         ..Default::default()
       },
     );
-    // if DEBUG.draw_rects {
-    //   self.get_hitbox().debug_draw(RED);
-    // }
+    if DEBUG.draw_rects {
+      self.get_rect().debug_draw(RED);
+    }
   }
 }
 
-pub fn spawn_effect(
-  effects: &mut Vec<Effects>,
-  textures: &Textures,
-  kind: EffectKind,
-  grid_pos: (usize, usize),
-) {
+/** Spawns a `kind` effect with its top left corner at `pos`, in level pixels. */
+pub fn spawn_effect(effects: &mut Vec<Effects>, kind: EffectKind, pos: (usize, usize)) {
   match kind {
-    EffectKind::LavaDrop => effects.push(Effects::LavaDrop(LavaDrop::new(grid_pos, textures))),
-    EffectKind::LavaSplash => {
-      effects.push(Effects::LavaSplash(LavaSplash::new(grid_pos, textures)))
-    }
+    EffectKind::LavaDrop => effects.push(Effects::LavaDrop(LavaDrop::new(pos))),
+    EffectKind::LavaSplash => effects.push(Effects::LavaSplash(LavaSplash::new(pos))),
   }
 }
 
@@ -110,57 +124,31 @@ pub struct LavaDrop {
   pos: (usize, usize),
   _pos: Vec2,
   draw_pos: Rect,
-  atlas: Option<FrameDrawing>,
-  default_texture: Texture2D,
+  age: f32,
   hitbox: Rect,
 }
 impl LavaDrop {
-  pub fn new(grid_pos: (usize, usize), textures: &Textures) -> LavaDrop {
-    let pos = grid_pos_to_pos(&grid_pos);
+  pub fn new(pos: (usize, usize)) -> LavaDrop {
     LavaDrop {
       kind: EffectKind::LavaDrop,
       pos,
       _pos: vec2(pos.0 as f32, pos.1 as f32),
       hitbox: Rect::new(pos.0, pos.1, pos.0 + 32, pos.1 + 32),
       draw_pos: Rect::new(8, 10, 24, 38),
-      atlas: None,
-      default_texture: textures.lava_drop,
+      age: 0.0,
     }
   }
   /** Returns false when not falling */
   fn move_y(&mut self, wrld: &World) -> bool {
-    //Update position and check for collisions.
-    self._pos.y += wrld.get_scaled_gravity();
-    let ydiff = self._pos.y - self.pos.1 as f32;
-    // println!("{:?}, {:?}, {}", self._pos.y, self.pos, ydiff);
-    if ydiff > 1.0 {
-      let mut y: usize = 0;
-
-      while y <= ydiff as usize {
-        let rect = Rect::new(
-          self.hitbox.left,
-          self.hitbox.top + 1,
-          self.hitbox.right,
-          self.hitbox.bottom + 1,
-        );
-        let rect_grid_pos = pos_to_grid_pos(&rect.tl());
-
-        if let Some(tile_below) = wrld.get_tile(&(rect_grid_pos.0 as usize), &(rect_grid_pos.1 + 1))
-        {
-          if tile_below.collide(&rect) {
-            //We have collided.
-            return false;
-          }
-        }
-
-        self.pos.1 += 1;
-        self.hitbox.top += 1;
-        self.hitbox.bottom += 1;
-        y += 1;
-      }
-      self._pos = vec2(self.pos.0 as f32, self.pos.1 as f32);
-    }
-    true
+    let fall = vec2(0.0, wrld.get_scaled_gravity());
+    //Drops fall through towers, only the ground stops them.
+    let aabb = Aabb::new(self._pos, self._pos + vec2(32.0, 32.0));
+    let sweep = Colliders::new(wrld, None).sweep(&aabb, fall);
+    self._pos += sweep.moved;
+    let pixel = self._pos.round().max(Vec2::ZERO);
+    self.pos = (pixel.x as usize, pixel.y as usize);
+    self.hitbox = aabb.offset(sweep.moved).to_rect();
+    sweep.contact.is_none()
   }
 }
 
@@ -173,15 +161,15 @@ impl Collidable for LavaDrop {
   }
 }
 impl Effect for LavaDrop {
-  fn get_atlas(&mut self) -> &mut Option<FrameDrawing> {
-    &mut self.atlas
+  fn get_sprite(&self) -> &'static str {
+    "lava_drop"
+  }
+  fn get_age(&self) -> f32 {
+    self.age
   }
   fn get_rect(&self) -> &Rect {
     &self.hitbox
   }
-  fn get_default_texture(&self) -> Texture2D {
-    self.default_texture
-  }
   fn get_draw_pos(&self) -> Rect {
     self.draw_pos + &self.pos
   }
@@ -191,7 +179,15 @@ impl Effect for LavaDrop {
   fn get_pos(&self) -> &(usize, usize) {
     &self.pos
   }
+  fn get_damage(&self) -> Damage {
+    Damage::Burst(LAVA_DROP_DAMAGE)
+  }
+  /** Splashes where it hit. */
+  fn on_hit(&self) -> Option<(EffectKind, (usize, usize))> {
+    Some((EffectKind::LavaSplash, self.pos))
+  }
   fn update(&mut self, wrld: &World) -> EffectUpdateReturn {
+    self.age += wrld.dt;
     let falling = self.move_y(wrld);
     if !falling {
       return EffectUpdateReturn::new(
@@ -200,7 +196,6 @@ impl Effect for LavaDrop {
       );
     }
 
-    self.draw(wrld);
     EffectUpdateReturn::keep()
   }
 }
@@ -215,20 +210,18 @@ pub struct LavaSplash {
   pos: (usize, usize),
   _pos: Vec2,
   draw_pos: Rect,
-  atlas: Option<FrameDrawing>,
-  default_texture: Texture2D,
+  age: f32,
   hitbox: Rect,
 }
 impl LavaSplash {
-  pub fn new(pos: (usize, usize), textures: &Textures) -> LavaSplash {
+  pub fn new(pos: (usize, usize)) -> LavaSplash {
     LavaSplash {
       kind: EffectKind::LavaSplash,
       pos,
       _pos: vec2(pos.0 as f32, pos.1 as f32),
       hitbox: Rect::new(pos.0, pos.1, pos.0 + 32, pos.1 + 32),
       draw_pos: Rect::new(0, 13, 32, 45),
-      default_texture: textures.lava_splash[0],
-      atlas: Some(FrameDrawing::new(textures.lava_splash.clone(), 0.1)),
+      age: 0.0,
     }
   }
 }
@@ -242,15 +235,15 @@ impl Collidable for LavaSplash {
   }
 }
 impl Effect for LavaSplash {
-  fn get_atlas(&mut self) -> &mut Option<FrameDrawing> {
-    &mut self.atlas
+  fn get_sprite(&self) -> &'static str {
+    "lava_splash"
+  }
+  fn get_age(&self) -> f32 {
+    self.age
   }
   fn get_rect(&self) -> &Rect {
     &self.hitbox
   }
-  fn get_default_texture(&self) -> Texture2D {
-    self.default_texture
-  }
   fn get_draw_pos(&self) -> Rect {
     self.draw_pos + &self.pos
   }
@@ -260,23 +253,65 @@ impl Effect for LavaSplash {
   fn get_pos(&self) -> &(usize, usize) {
     &self.pos
   }
+  fn get_damage(&self) -> Damage {
+    Damage::PerSecond(LAVA_SPLASH_DPS)
+  }
   fn update(&mut self, wrld: &World) -> EffectUpdateReturn {
-    self.draw(wrld);
-    // println!("Update LavaSplash {:?}", self.get_pos());
-    self.get_hitbox().debug_draw(RED);
-
-    if let Some(atlas) = self.get_atlas() {
-      if atlas.is_end() {
-        EffectUpdateReturn::abandon()
-      } else {
-        EffectUpdateReturn::keep()
-      }
-    } else {
+    self.age += wrld.dt;
+    if self.age >= LAVA_SPLASH_TIME {
       EffectUpdateReturn::abandon()
+    } else {
+      EffectUpdateReturn::keep()
     }
   }
 }
 
-pub fn has_effect_collision(effects: &Vec<Effects>, hitbox: Rect) -> bool {
-  effects.iter().any(|e| e.get_rect().collide(&hitbox))
+/** Rebuilds `index` with the position of every effect in `effects`. */
+pub fn index_effects(effects: &[Effects], index: &mut SpatialHash<usize>) {
+  index.clear();
+  for (i, effect) in effects.iter().enumerate() {
+    index.insert(i, effect.get_rect());
+  }
+}
+
+/**
+ * Hurts the enemies touching effects over a tick of `dt` seconds. Bursts that hit are replaced
+ * by what they spawn on hit. `index` must be up to date with `effects`, see `index_effects`, and
+ * is not once this returns.
+ */
+pub fn damage_enemies(
+  effects: &mut Vec<Effects>,
+  index: &SpatialHash<usize>,
+  enemies: &mut [Enemy],
+  dt: f32,
+) {
+  let mut used = vec![false; effects.len()];
+  for enemy in enemies.iter_mut() {
+    let rect = *enemy.get_rect();
+    for i in index.query(&rect) {
+      if enemy.is_dead() || !effects[i].get_rect().intersecting(&rect) {
+        continue;
+      }
+      match effects[i].get_damage() {
+        Damage::Burst(amount) => {
+          enemy.hurt(amount);
+          used[i] = true;
+        }
+        Damage::PerSecond(amount) => enemy.hurt(amount * dt),
+      }
+    }
+  }
+
+  let mut spawns = Vec::new();
+  let mut used = used.into_iter();
+  effects.retain(|effect| {
+    let used = used.next().unwrap_or(false);
+    if used {
+      spawns.extend(effect.on_hit());
+    }
+    !used
+  });
+  for (kind, pos) in spawns {
+    spawn_effect(effects, kind, pos);
+  }
 }
@@ -1,9 +1,9 @@
 use macroquad::prelude::Vec2;
 use std::ops::{Add, Div, Mul, Sub};
 
-pub fn map_range<T: Copy>(from_range: (T, T), to_range: (T, T), s: T) -> T
+pub fn map_range<T>(from_range: (T, T), to_range: (T, T), s: T) -> T
 where
-  T: Add<T, Output = T> + Sub<T, Output = T> + Mul<T, Output = T> + Div<T, Output = T>,
+  T: Copy + Add<T, Output = T> + Sub<T, Output = T> + Mul<T, Output = T> + Div<T, Output = T>,
 {
   to_range.0 + (s - from_range.0) * (to_range.1 - to_range.0) / (from_range.1 - from_range.0)
 }
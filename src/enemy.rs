@@ -1,15 +1,19 @@
+use enum_map::{Enum, EnumMap};
 use macroquad::{
-  prelude::{vec2, Vec2, GREEN, WHITE},
-  texture::{draw_texture_ex, DrawTextureParams, Texture2D},
+  prelude::{vec2, Color, Vec2, GREEN},
+  texture::DrawTextureParams,
 };
+use serde::Deserialize;
+use std::collections::HashMap;
 
 use crate::{
   deb::DEBUG,
-  effects::{has_effect_collision, Effects},
   emath::pos_to_grid_pos,
+  loading::Textures,
+  physics::{Aabb, Colliders, Hit},
   rect::{Collidable, Rect},
   tile::TileType,
-  tower::Towers,
+  tower::{TowerType, Towers},
   wrld::World,
 };
 
@@ -19,211 +23,572 @@ pub enum Facing {
   Right,
 }
 
-const WALKING_SPEED: f32 = 48.0;
+/** Seconds the turn animation plays after an enemy turns around. */
+pub const TURN_TIME: f32 = 0.15;
+/** Seconds a dead enemy stays on screen, long enough for its death animation. */
+pub const DEATH_TIME: f32 = 0.5;
+/** Seconds an enemy flashes after taking damage. */
+const FLASH_TIME: f32 = 0.1;
+/** Drawn instead of the tint while flashing. */
+const FLASH_COLOR: Color = Color::new(1.0, 0.3, 0.3, 1.0);
+
+/** How an enemy type gets around, on top of walking and turning at walls. */
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Movement {
+  #[default]
+  Walk,
+  /** Leaps gaps up to this many tiles wide instead of falling in. */
+  Jump(usize),
+  /** Climbs steps one tile high instead of turning around. */
+  Climb,
+  /** Never falls, but drifts up or down to the height of the nearest goal. */
+  Float,
+  /** Pushes against a blocker down for this many seconds, then digs through it. */
+  Dig(f32),
+}
+
+/** What an enemy is doing, picks the animation it plays. */
+#[derive(Enum, Debug, PartialEq, Clone, Copy)]
+pub enum Pose {
+  Walk,
+  Fall,
+  Turn,
+  Death,
+}
+
+impl Pose {
+  /** Sprite id of the pose's animation for enemies drawn with `sprite`. */
+  pub fn sprite(&self, sprite: &str) -> String {
+    let suffix = match self {
+      Pose::Walk => "walk",
+      Pose::Fall => "fall",
+      Pose::Turn => "turn",
+      Pose::Death => "death",
+    };
+    format!("{}_{}", sprite, suffix)
+  }
+}
+
+/** Spawned by spans that do not name a kind. */
+pub static DEFAULT_ENEMY: &str = "walker";
+
+/** One entry of `assets/enemies.toml`. */
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct EnemyDef {
+  /** Prefix of the sprite ids of its animations, see `Pose::sprite`. */
+  pub sprite: String,
+  /** `[r, g, b]` the sprite is multiplied with. */
+  pub tint: Option<[u8; 3]>,
+  /** `[left, top, right, bottom]` in pixels, relative to the enemy's position. */
+  pub hitbox: [usize; 4],
+  pub draw: [usize; 4],
+  /** Pixels per second. */
+  pub speed: f32,
+  pub health: usize,
+  /** Health the player loses when the enemy reaches the goal. */
+  pub damage: usize,
+  /** Scales the world's gravity. */
+  #[serde(default = "default_gravity")]
+  pub gravity: f32,
+  #[serde(default)]
+  pub movement: Movement,
+}
+
+fn default_gravity() -> f32 {
+  1.0
+}
+
+fn as_rect(offsets: &[usize; 4]) -> Rect {
+  Rect::new(offsets[0], offsets[1], offsets[2], offsets[3])
+}
+
+/** Every enemy type, keyed by the id spans name. */
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnemyTable {
+  defs: HashMap<String, EnemyDef>,
+}
+
+impl EnemyTable {
+  /**
+   * Fails with the kind and what is wrong with its definition, or when the default enemy the
+   * spawn key calls in early is missing.
+   */
+  pub fn new(defs: HashMap<String, EnemyDef>) -> Result<EnemyTable, (String, String)> {
+    if !defs.contains_key(DEFAULT_ENEMY) {
+      return Err((DEFAULT_ENEMY.to_owned(), "no definition".to_owned()));
+    }
+    for (kind, def) in &defs {
+      let invalid = [
+        ("a speed of zero or less", def.speed <= 0.0),
+        ("a gravity of zero or less", def.gravity <= 0.0),
+        ("no health", def.health == 0),
+        ("a jump over no tiles", def.movement == Movement::Jump(0)),
+        (
+          "a negative dig time",
+          matches!(def.movement, Movement::Dig(time) if time < 0.0),
+        ),
+      ];
+      if let Some((problem, _)) = invalid.iter().find(|(_, invalid)| *invalid) {
+        return Err((kind.clone(), problem.to_string()));
+      }
+    }
+    Ok(EnemyTable { defs })
+  }
+  pub fn get(&self, kind: &str) -> Option<&EnemyDef> {
+    self.defs.get(kind)
+  }
+  pub fn iter(&self) -> impl Iterator<Item = (&String, &EnemyDef)> {
+    self.defs.iter()
+  }
+}
 
 pub struct Enemy {
-  //Fractional position.
+  /** Exact position, which the physics moves. */
   _pos: Vec2,
-  //Pixel position.
+  /** Pixel position, `_pos` rounded. */
   pub pos: (usize, usize),
   pub grid_pos: (usize, usize),
-  texture: Texture2D,
   /** Offset and size in pixels. */
   hitbox: Rect,
   facing: Facing,
   rotation: f32,
   rect: Rect,
   draw_pos: Rect,
+  /** Id of its type in the enemy table. */
+  kind: String,
+  /** Sprite id of every pose's animation. */
+  sprites: EnumMap<Pose, String>,
+  tint: Color,
+  speed: f32,
+  gravity: f32,
+  damage: usize,
+  /** Hit points left, dead at zero. */
+  health: f32,
+  /** Seconds left of the flash after the last hit. */
+  flash: f32,
+  /** Seconds since it spawned. */
+  age: f32,
+  pose: Pose,
+  /** Age at which the current pose started, its animation plays from there. */
+  pose_since: f32,
+  /** Seconds left of the turn animation. */
+  turning: f32,
+  movement: Movement,
+  /** Whether it fell during the last update. */
+  falling: bool,
+  /** In the air over a gap it leaps. */
+  jumping: bool,
+  /** Pixels left to go up the step it is climbing. */
+  climb_left: f32,
+  /** Age at which it started pushing against the blocker down in its way. */
+  digging_since: Option<f32>,
+  /** Grid position of the tower it dug through, which it passes from then on. */
+  dug: Option<(usize, usize)>,
 }
 
 impl Enemy {
-  pub fn new(pos: (usize, usize), hitbox: Rect, draw_pos: Rect, texture: Texture2D) -> Enemy {
-    let mut rect = hitbox.clone();
-    rect.left += pos.0;
-    rect.top += pos.1;
-    rect.right += pos.0;
-    rect.bottom += pos.1;
+  /** A `kind` enemy standing at `pos`, with the stats of its definition `def`. */
+  pub fn new(kind: &str, def: &EnemyDef, pos: (usize, usize)) -> Enemy {
+    let hitbox = as_rect(&def.hitbox);
+    let tint = def.tint.unwrap_or([255, 255, 255]);
+    let rect = hitbox + &pos;
 
     Enemy {
       _pos: vec2(pos.0 as f32, pos.1 as f32),
       pos,
       grid_pos: pos_to_grid_pos(&pos),
-      texture,
       hitbox,
       rotation: 0.0,
       facing: Facing::Left,
       rect,
-      draw_pos,
+      draw_pos: as_rect(&def.draw),
+      kind: kind.to_owned(),
+      sprites: enum_map! { pose => pose.sprite(&def.sprite) },
+      tint: Color::from_rgba(tint[0], tint[1], tint[2], 255),
+      speed: def.speed,
+      gravity: def.gravity,
+      damage: def.damage,
+      health: def.health as f32,
+      flash: 0.0,
+      age: 0.0,
+      pose: Pose::Fall,
+      pose_since: 0.0,
+      turning: 0.0,
+      movement: def.movement,
+      falling: true,
+      jumping: false,
+      climb_left: 0.0,
+      digging_since: None,
+      dug: None,
     }
   }
-  pub fn update_rect(&mut self) {
-    self.rect = Rect::new(
-      self.pos.0 + self.hitbox.left,
-      self.pos.1 + self.hitbox.top,
-      self.pos.0 + self.hitbox.right,
-      self.pos.1 + self.hitbox.bottom,
-    );
+  /** Moves to `pos`, keeping the pixel and grid position and the hitbox in step. */
+  fn set_pos(&mut self, pos: Vec2) {
+    self._pos = pos;
+    let pixel = pos.round().max(Vec2::ZERO);
+    self.pos = (pixel.x as usize, pixel.y as usize);
+    self.grid_pos = pos_to_grid_pos(&self.pos);
+    self.rect = self.aabb().to_rect();
+  }
+  /** Exact hitbox in level pixels. */
+  fn aabb(&self) -> Aabb {
+    Aabb::at(self._pos, &self.hitbox)
+  }
+  /** What it collides with, which leaves out the tower it dug through. */
+  fn colliders<'a>(&self, wrld: &'a World, towers: &'a Towers) -> Colliders<'a> {
+    Colliders::new(wrld, Some(towers)).skipping(self.dug)
   }
 
   /** Returns (falling, keep) */
   fn move_y(&mut self, wrld: &mut World, towers: &Towers) -> (bool, bool) {
-    //Update position and check for collisions.
-    self._pos.y += wrld.get_scaled_gravity();
-    let ydiff = self._pos.y - self.pos.1 as f32;
-    let mut falling = true;
-    if ydiff > 1.0 {
-      let mut y: usize = 0;
-
-      while y <= ydiff as usize {
-        let rect = Rect::new(
-          self.pos.0 + self.hitbox.left,
-          self.pos.1 + self.hitbox.top + 1,
-          self.pos.0 + self.hitbox.right,
-          self.pos.1 + self.hitbox.bottom + 1,
-        );
-        let rect_grid_pos = pos_to_grid_pos(&rect.tl());
-
-        if let Some(x) = towers.get_collided_tower(&rect) {
-          println!("{}", x);
-          //We have collided with a tower.
-          self._pos.y = self.pos.1 as f32;
-          falling = false;
-          break;
-        }
-        for offset in -1..2 {
-          if let Some(tile_below) = wrld.get_tile(
-            &((rect_grid_pos.0 as isize + offset) as usize),
-            &(rect_grid_pos.1 + 1),
-          ) {
-            if tile_below.collide(&rect) {
-              //We have collided.
-              if tile_below.kind() == &TileType::Goal {
-                wrld.health -= 1;
-                return (true, false);
-              }
-              self._pos.y = self.pos.1 as f32;
-              return (false, true);
-            }
-          }
-        }
-        self.pos.1 += 1;
-        self.grid_pos = pos_to_grid_pos(&self.pos);
-        y += 1;
+    let fall = vec2(0.0, wrld.get_scaled_gravity() * self.gravity);
+    let sweep = self.colliders(wrld, towers).sweep(&self.aabb(), fall);
+    self.set_pos(self._pos + sweep.moved);
+    match sweep.contact.map(|contact| contact.hit) {
+      None => (true, true),
+      Some(Hit::Tile {
+        kind: TileType::Goal,
+        ..
+      }) => {
+        wrld.damage(self.damage);
+        (true, false)
       }
+      Some(_) => (false, true),
     }
-    (falling, true)
   }
 
   /** Returns keep */
-  fn move_x(&mut self, wrld: &mut World, towers: &Towers, effects: &Vec<Effects>) -> bool {
-    let mut keep = true;
-    let xdir: isize = if self.facing == Facing::Left { -1 } else { 1 };
-    self._pos.x += xdir as f32 * WALKING_SPEED * wrld.dt;
-    // self._pos.x += (xdir as f32 * WALKING_SPEED * wrld.dt).max(1.0);
-    let xdiff = (self._pos.x - self.pos.0 as f32).abs();
-    if xdiff > 1.0 {
-      //Update position and check for collisions.
-      let mut x: usize = 0;
-      while x <= xdiff as usize {
-        let rect = Rect::new(
-          (self.pos.0 as isize + self.hitbox.left as isize + 1 * xdir).max(0) as usize,
-          self.pos.1 + self.hitbox.top,
-          (self.pos.0 as isize + self.hitbox.right as isize + 1 * xdir).max(4) as usize,
-          self.pos.1 + self.hitbox.bottom,
-        );
-
-        if has_effect_collision(effects, rect) {
-          //Kill!
-          return false;
+  fn move_x(&mut self, wrld: &mut World, towers: &Towers) -> bool {
+    let xdir = if self.facing == Facing::Left {
+      -1.0
+    } else {
+      1.0
+    };
+    let walk = vec2(xdir * self.speed * wrld.dt, 0.0);
+    let colliders = self.colliders(wrld, towers);
+    let sweep = colliders.sweep(&self.aabb(), walk);
+    self.set_pos(self._pos + sweep.moved);
+    if sweep.moved.x != 0.0 {
+      self.digging_since = None;
+    }
+
+    match sweep.contact.map(|contact| contact.hit) {
+      None => {}
+      Some(Hit::Tile {
+        kind: TileType::Goal,
+        ..
+      }) => {
+        wrld.damage(self.damage);
+        return false;
+      }
+      Some(Hit::Tile { grid_pos, .. }) => {
+        if self.movement == Movement::Climb {
+          self.climb_left = self.step_height(&colliders, grid_pos);
         }
-        if let Some(_twr) = towers.get_collided_tower(&rect) {
-          //We have collided with a tower.
-          self._pos.x = self.pos.0 as f32;
-          self.facing = if self.facing == Facing::Right {
-            Facing::Left
-          } else {
-            Facing::Right
-          };
-          break;
+        if self.climb_left <= 0.0 {
+          self.turn();
         }
-        // effects.retain(|e| e)
-
-        // effects.into_iter().find(|e| e.)
-        // if effects.iter().find(|effect| effect) {
-
-        // }
-
-        let rect_grid_pos = pos_to_grid_pos(&rect.tl());
-        if let Some(next_tile) = wrld.get_tile(
-          &((rect_grid_pos.0 as isize + xdir) as usize),
-          &rect_grid_pos.1,
-        ) {
-          // println!(
-          //   "{:?}, {:?}, {:?}",
-          //   next_tile.kind(),
-          //   next_tile.grid_pos(),
-          //   rect
-          // );
-          if next_tile.collide(&rect) {
-            //We have collided with a tile.
-            if next_tile.kind() == &TileType::Goal {
-              wrld.health -= 1;
-              keep = false;
-              break;
-            }
-
-            self._pos.x = self.pos.0 as f32;
-            self.facing = if self.facing == Facing::Right {
-              Facing::Left
-            } else {
-              Facing::Right
-            };
-            break;
-          }
+      }
+      Some(Hit::Tower { grid_pos, kind }) => {
+        //Diggers push against blocker downs until they are through instead.
+        let through = self.dig(kind, grid_pos);
+        if !through && self.digging_since.is_none() {
+          self.turn();
         }
-        self.pos.0 = (self.pos.0 as isize + xdir).max(1) as usize;
-        self.grid_pos = pos_to_grid_pos(&self.pos);
-        x += 1;
       }
+      Some(Hit::Edge) => self.turn(),
     }
-    keep
+    true
+  }
+
+  fn turn(&mut self) {
+    self.facing = match self.facing {
+      Facing::Left => Facing::Right,
+      Facing::Right => Facing::Left,
+    };
+    self.turning = TURN_TIME;
+  }
+
+  /** Pushes against the `kind` tower at `grid_pos`. Returns true once a digger is through it. */
+  fn dig(&mut self, kind: TowerType, grid_pos: (usize, usize)) -> bool {
+    let delay = match self.movement {
+      Movement::Dig(delay) if kind == TowerType::BlockerDown => delay,
+      _ => return false,
+    };
+    let since = *self.digging_since.get_or_insert(self.age);
+    if self.age - since < delay {
+      return false;
+    }
+    self.dug = Some(grid_pos);
+    self.digging_since = None;
+    true
+  }
+
+  /** Whether `aabb` would stand on a tile or a tower. */
+  fn stands(colliders: &Colliders, aabb: &Aabb) -> bool {
+    let feet = Aabb::new(
+      vec2(aabb.min.x, aabb.max.y),
+      vec2(aabb.max.x, aabb.max.y + 1.0),
+    );
+    colliders.blocked(&feet)
+  }
+
+  /** Whether walking `gap` tiles on finds ground with room to stand on it. */
+  fn can_jump(&self, colliders: &Colliders, gap: usize) -> bool {
+    let xdir = if self.facing == Facing::Left {
+      -1.0
+    } else {
+      1.0
+    };
+    for tiles in 1..=gap {
+      let landing = self.aabb().offset(vec2(xdir * 32.0 * tiles as f32, 0.0));
+      if colliders.blocked(&landing) {
+        return false;
+      }
+      if Enemy::stands(colliders, &landing) {
+        return true;
+      }
+    }
+    false
+  }
+
+  /** Pixels up to the top of the step at `step`, or zero if there is no room to climb it. */
+  fn step_height(&self, colliders: &Colliders, step: (usize, usize)) -> f32 {
+    let aabb = self.aabb();
+    let top = (step.1 * 32) as f32;
+    let height = aabb.max.y - top;
+    if height <= 0.0 || height > 32.0 {
+      return 0.0;
+    }
+    //Room for the whole body on top of the step and over the way there.
+    let above = Aabb::new(
+      vec2(
+        aabb.min.x.min(step.0 as f32 * 32.0),
+        top - (aabb.max.y - aabb.min.y),
+      ),
+      vec2(aabb.max.x.max((step.0 + 1) as f32 * 32.0), top),
+    );
+    if colliders.blocked(&above) {
+      return 0.0;
+    }
+    height
+  }
+
+  /**
+   * Moves a floater toward the height at which it stands in the nearest goal tile, so it reaches
+   * goals below or above its spawn. Returns keep.
+   */
+  fn drift(&mut self, wrld: &mut World, towers: &Towers) -> bool {
+    let goal = match wrld.get_lvl().nearest_goal(self.pos) {
+      Some(goal) => goal,
+      None => return true,
+    };
+    let aabb = self.aabb();
+    let max_step = self.speed * wrld.dt;
+    let dy = ((goal.1 + 1) as f32 * 32.0 - aabb.max.y).clamp(-max_step, max_step);
+    if dy == 0.0 {
+      return true;
+    }
+    let sweep = self.colliders(wrld, towers).sweep(&aabb, vec2(0.0, dy));
+    self.set_pos(self._pos + sweep.moved);
+    match sweep.contact.map(|contact| contact.hit) {
+      Some(Hit::Tile {
+        kind: TileType::Goal,
+        ..
+      }) => {
+        wrld.damage(self.damage);
+        false
+      }
+      _ => true,
+    }
+  }
+
+  /** Returns whether a jumper stays in the air this update instead of falling. */
+  fn hovers(&mut self, wrld: &World, towers: &Towers) -> bool {
+    match self.movement {
+      Movement::Jump(gap) => {
+        let colliders = self.colliders(wrld, towers);
+        if Enemy::stands(&colliders, &self.aabb()) {
+          self.jumping = false;
+        } else if !self.jumping && !self.falling {
+          self.jumping = self.can_jump(&colliders, gap);
+        }
+        self.jumping
+      }
+      _ => false,
+    }
+  }
+
+  fn climb(&mut self, dt: f32) {
+    let step = (self.speed * dt).min(self.climb_left);
+    self.climb_left -= step;
+    self.set_pos(self._pos - vec2(0.0, step));
   }
 
   /** Returns keep */
-  pub fn update(&mut self, wrld: &mut World, towers: &Towers, effects: &Vec<Effects>) -> bool {
-    let (falling, mut keep) = self.move_y(wrld, towers);
+  pub fn update(&mut self, wrld: &mut World, towers: &Towers) -> bool {
+    self.age += wrld.dt;
+    self.flash = (self.flash - wrld.dt).max(0.0);
+    self.turning = (self.turning - wrld.dt).max(0.0);
+    if self.climb_left > 0.0 {
+      self.climb(wrld.dt);
+      self.update_pose();
+      return true;
+    }
+
+    let (falling, mut keep) = if self.movement == Movement::Float {
+      (false, self.drift(wrld, towers))
+    } else if self.hovers(wrld, towers) {
+      (false, true)
+    } else {
+      self.move_y(wrld, towers)
+    };
+    self.falling = falling;
     if keep && !falling {
-      keep = self.move_x(wrld, towers, effects);
+      keep = self.move_x(wrld, towers);
     }
 
     if keep {
-      self.update_rect();
-      self.draw(&wrld);
-      return true;
+      self.update_pose();
+    }
+    keep
+  }
+
+  /** Starts the animation of what it is doing now, unless that is already playing. */
+  fn update_pose(&mut self) {
+    let pose = if self.falling || self.jumping {
+      Pose::Fall
+    } else if self.turning > 0.0 {
+      Pose::Turn
     } else {
-      return false;
+      Pose::Walk
+    };
+    if pose != self.pose {
+      self.pose = pose;
+      self.pose_since = self.age;
     }
   }
 
-  pub fn draw(&self, wrld: &World) {
+  pub fn get_kind(&self) -> &str {
+    &self.kind
+  }
+  pub fn get_facing(&self) -> Facing {
+    self.facing
+  }
+  pub fn get_pose(&self) -> Pose {
+    self.pose
+  }
+  pub fn get_health(&self) -> f32 {
+    self.health
+  }
+  /** Takes `amount` hit points and starts a flash. */
+  pub fn hurt(&mut self, amount: f32) {
+    self.health = (self.health - amount).max(0.0);
+    self.flash = FLASH_TIME;
+  }
+  pub fn is_dead(&self) -> bool {
+    self.health <= 0.0
+  }
+  /** Hitbox in level pixels. */
+  pub fn get_rect(&self) -> &Rect {
+    &self.rect
+  }
+  /** Where the sprite goes, in level pixels. */
+  pub fn get_draw_rect(&self) -> Rect {
+    self.draw_pos + &self.pos
+  }
+  /** Draws in level pixels, under the camera. */
+  pub fn draw(&self, texs: &Textures) {
     if DEBUG.draw_rects {
       self.rect.debug_draw(GREEN);
     }
-    let tl = self.draw_pos.tl();
-    draw_texture_ex(
-      self.texture,
-      ((self.pos.0 + tl.0) as f32 + wrld.scroll_pos.x) * wrld.zoom,
-      ((self.pos.1 + tl.1) as f32 + wrld.scroll_pos.y) * wrld.zoom,
-      WHITE,
+    let color = if self.flash > 0.0 {
+      FLASH_COLOR
+    } else {
+      self.tint
+    };
+    draw_animation(
+      texs,
+      &self.sprites[self.pose],
+      self.age - self.pose_since,
+      &self.get_draw_rect(),
+      color,
       DrawTextureParams {
-        dest_size: Some(vec2(
-          wrld.zoom * self.draw_pos.width() as f32,
-          wrld.zoom * self.draw_pos.height() as f32,
-        )),
         rotation: self.rotation,
+        flip_x: self.facing == Facing::Right,
+        ..Default::default()
+      },
+    );
+  }
+}
+
+/** Draws the frame of `sprite` shown `time` seconds in, stretched over `rect` in level pixels. */
+fn draw_animation(
+  texs: &Textures,
+  sprite: &str,
+  time: f32,
+  rect: &Rect,
+  color: Color,
+  params: DrawTextureParams,
+) {
+  texs.frame(sprite, time).draw(
+    rect.left as f32,
+    rect.top as f32,
+    color,
+    DrawTextureParams {
+      dest_size: Some(vec2(rect.width() as f32, rect.height() as f32)),
+      ..params
+    },
+  );
+}
+
+/**
+ * A dead enemy playing its death animation where it died. Only drawn; the simulation drops
+ * enemies as soon as they die.
+ */
+pub struct Corpse {
+  sprite: String,
+  tint: Color,
+  draw_rect: Rect,
+  facing: Facing,
+  /** Seconds since it died. */
+  age: f32,
+}
+
+impl Corpse {
+  /** The corpse of an enemy of definition `def` that died at `pos` facing `facing`. */
+  pub fn new(def: &EnemyDef, pos: (usize, usize), facing: Facing) -> Corpse {
+    let tint = def.tint.unwrap_or([255, 255, 255]);
+    Corpse {
+      sprite: Pose::Death.sprite(&def.sprite),
+      tint: Color::from_rgba(tint[0], tint[1], tint[2], 255),
+      draw_rect: as_rect(&def.draw) + &pos,
+      facing,
+      age: 0.0,
+    }
+  }
+  /** Returns keep, false once the death animation is over. */
+  pub fn update(&mut self, dt: f32) -> bool {
+    self.age += dt;
+    self.age < DEATH_TIME
+  }
+  pub fn get_draw_rect(&self) -> &Rect {
+    &self.draw_rect
+  }
+  /** Draws in level pixels, under the camera. */
+  pub fn draw(&self, texs: &Textures) {
+    draw_animation(
+      texs,
+      &self.sprite,
+      self.age,
+      &self.draw_rect,
+      self.tint,
+      DrawTextureParams {
+        flip_x: self.facing == Facing::Right,
         ..Default::default()
       },
-    )
+    );
   }
 }
 
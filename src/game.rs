@@ -0,0 +1,416 @@
+use macroquad::{prelude::KeyCode, time::get_frame_time};
+use std::{
+  mem::take,
+  path::PathBuf,
+  time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+  bindings::{Action, Binding, Bindings, PICK_LEVEL},
+  deb::{draw_debug_texts, DebugSettings},
+  gamepad,
+  input::{self, Command},
+  level::{Level, Levels},
+  loading::{LoadError, Textures},
+  render::{self, TileCache},
+  replay::{Replay, ReplayPlayer, REPLAY_PATH},
+  save::SaveProfile,
+  screens::{self, MenuItem, Notices},
+  sim::{Clock, Outcome, Sim, TICK},
+  view::View,
+};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum GameState {
+  Title,
+  Controls,
+  LevelSelect,
+  Playing,
+  Paused,
+  Won,
+  Lost,
+}
+
+/** Owns the simulation and moves between the menus and the level being played. */
+pub struct Game {
+  state: GameState,
+  levels: Levels,
+  sim: Sim,
+  view: View,
+  tiles: TileCache,
+  clock: Clock,
+  /** Input commands waiting for the next tick. */
+  pending: Vec<Command>,
+  player: Option<ReplayPlayer>,
+  profile: SaveProfile,
+  bindings: Bindings,
+  /** The action on the controls screen waiting for a new key. */
+  rebinding: Option<Action>,
+  notices: Notices,
+  deb_state: DebugSettings,
+}
+
+impl Game {
+  pub fn new(
+    levels: Levels,
+    profile: SaveProfile,
+    bindings: Bindings,
+    deb_state: DebugSettings,
+  ) -> Result<Game, LoadError> {
+    let first = levels.get_level(0).ok_or(LoadError::EmptyCampaign)?;
+    Ok(Game {
+      state: GameState::Title,
+      sim: Sim::new(first),
+      levels,
+      view: View::new(),
+      tiles: TileCache::new(),
+      clock: Clock::new(),
+      pending: Vec::new(),
+      player: None,
+      profile,
+      bindings,
+      rebinding: None,
+      notices: Notices::default(),
+      deb_state,
+    })
+  }
+
+  /** Skips the menus and plays `replay` back instead of reading input. */
+  pub fn play_replay(&mut self, replay: Replay) -> Result<(), LoadError> {
+    let mut lvl = self
+      .levels
+      .find_level(&replay.level)
+      .ok_or_else(|| LoadError::Replay {
+        message: format!("level {} is not in the campaign", replay.level),
+      })?;
+    lvl.reseed(replay.seed);
+    self.start(lvl);
+    self.player = Some(ReplayPlayer::new(replay));
+    Ok(())
+  }
+
+  /** Unlocked through the save profile, or by having completed everything the level requires. */
+  fn is_unlocked(&self, lvl: &Level) -> bool {
+    self.profile.is_unlocked(&lvl.name) || self.levels.is_unlocked(lvl, &self.profile.completed())
+  }
+
+  /** Stores the win in the save profile and unlocks the levels it leads to. */
+  fn record_win(&mut self) {
+    let lvl = self.sim.wrld.get_lvl();
+    self.profile.record_win(
+      &lvl.name,
+      self.sim.wrld.health,
+      self.sim.towers.get_built_count(),
+      self.sim.wrld.tick,
+    );
+    let completed = self.profile.completed();
+    for next in self.levels.next_levels(lvl) {
+      if self.levels.is_unlocked(next, &completed) {
+        self.profile.unlock(&next.name);
+      }
+    }
+    if let Err(e) = self.profile.save() {
+      self.notify(format!("Could not write save file {}", e));
+    }
+  }
+
+  fn start_level(&mut self, index: usize) {
+    if let Some(lvl) = self.levels.get_level(index) {
+      self.start(lvl);
+    }
+  }
+  fn start(&mut self, lvl: Level) {
+    self.sim.set_level(lvl);
+    self.view = View::new();
+    self.tiles.clear();
+    self.clock = Clock::new();
+    self.pending.clear();
+    self.player = None;
+    self.state = GameState::Playing;
+  }
+
+  /** Updates and draws one rendered frame of the current state. */
+  pub fn frame(&mut self, texs: &Textures) {
+    gamepad::update();
+    match self.state {
+      GameState::Title => self.title(),
+      GameState::Controls => self.controls(),
+      GameState::LevelSelect => self.level_select(),
+      GameState::Playing => self.playing(texs),
+      GameState::Paused => self.paused(texs),
+      GameState::Won => self.won(texs),
+      GameState::Lost => self.lost(texs),
+    }
+    self.notices.update(get_frame_time());
+  }
+
+  /** Shows `text` on top of whatever screen is up for a few seconds. */
+  pub fn notify(&mut self, text: String) {
+    self.notices.push(text);
+  }
+
+  fn save_bindings(&mut self) {
+    if let Err(e) = self.bindings.save() {
+      self.notify(format!("Could not write bindings file {}", e));
+    }
+  }
+
+  fn draw_level(&mut self, texs: &Textures) {
+    render::draw(&mut self.tiles, &self.sim, texs, &self.view, &self.bindings);
+    draw_debug_texts(&self.deb_state, &self.sim.wrld, &self.view);
+  }
+
+  fn title(&mut self) {
+    let items = [
+      MenuItem {
+        label: "Play".to_owned(),
+        enabled: true,
+      },
+      MenuItem {
+        label: "Controls".to_owned(),
+        enabled: true,
+      },
+    ];
+    match screens::menu("TD Down", &items) {
+      Some(0) => self.state = GameState::LevelSelect,
+      Some(_) => self.state = GameState::Controls,
+      None if self.bindings.is_released(Action::Confirm) => self.state = GameState::LevelSelect,
+      None => {}
+    }
+  }
+
+  /** Lists every action with its bindings. Clicking one binds it to the next key pressed. */
+  fn controls(&mut self) {
+    let mut items: Vec<MenuItem> = Action::all()
+      .map(|action| MenuItem {
+        label: if self.rebinding == Some(action) {
+          format!("{}: press a key or button...", action.label())
+        } else {
+          format!("{}: {}", action.label(), self.bindings.describe(action))
+        },
+        enabled: self.rebinding.is_none(),
+      })
+      .collect();
+    let reset = items.len();
+    items.push(MenuItem {
+      label: "Reset to defaults".to_owned(),
+      enabled: self.rebinding.is_none(),
+    });
+    items.push(MenuItem {
+      label: "Back".to_owned(),
+      enabled: self.rebinding.is_none(),
+    });
+    let clicked = screens::menu_compact("Controls", &items);
+
+    if let Some(action) = self.rebinding {
+      //Escape always cancels, so no binding can lock the player out of this screen.
+      match Binding::last_pressed() {
+        Some(Binding::Key(KeyCode::Escape)) => self.rebinding = None,
+        Some(binding) => {
+          self.bindings.rebind(action, binding);
+          self.save_bindings();
+          self.rebinding = None;
+        }
+        None => {}
+      }
+      return;
+    }
+    match clicked {
+      Some(i) if i < reset => self.rebinding = Action::all().nth(i),
+      Some(i) if i == reset => {
+        self.bindings = Bindings::defaults();
+        self.save_bindings();
+      }
+      Some(_) => self.state = GameState::Title,
+      None if self.bindings.is_released(Action::Back) => self.state = GameState::Title,
+      None => {}
+    }
+  }
+
+  fn level_select(&mut self) {
+    let items: Vec<MenuItem> = self
+      .levels
+      .iter()
+      .map(|lvl| MenuItem {
+        label: level_label(lvl, &self.profile),
+        enabled: self.is_unlocked(lvl),
+      })
+      .collect();
+    let mut selected = screens::menu("Select level", &items);
+
+    for (i, action) in PICK_LEVEL.iter().enumerate().take(self.levels.len()) {
+      if self.bindings.is_released(*action) && items[i].enabled {
+        selected = Some(i);
+      }
+    }
+
+    if let Some(index) = selected {
+      self.start_level(index);
+    } else if self.bindings.is_released(Action::Back) {
+      self.state = GameState::Title;
+    }
+  }
+
+  fn playing(&mut self, texs: &Textures) {
+    self.view.update(self.sim.wrld.get_lvl(), &self.bindings);
+    if self.player.is_none() {
+      let commands = input::poll_commands(
+        &self.sim.wrld,
+        &self.sim.towers,
+        &mut self.view,
+        &self.bindings,
+      );
+      self.pending.extend(commands);
+    }
+
+    //Input commands go to the first tick of the frame, or wait for a frame that runs one.
+    for _ in 0..self.clock.advance(get_frame_time(), self.sim.wrld.speed) {
+      let commands = match &mut self.player {
+        Some(p) => p.take(self.sim.wrld.tick),
+        None => take(&mut self.pending),
+      };
+      self.sim.tick(&commands);
+      self
+        .view
+        .follow_tick(self.sim.wrld.get_lvl(), &self.sim.events, TICK);
+
+      if let Some(outcome) = self.sim.outcome() {
+        self.state = match outcome {
+          Outcome::Won => {
+            if self.player.is_none() {
+              self.record_win();
+            }
+            GameState::Won
+          }
+          Outcome::Lost => GameState::Lost,
+        };
+        break;
+      }
+    }
+
+    if self.bindings.is_released(Action::SaveReplay) {
+      let notice = match save_replay(&self.sim.replay) {
+        Ok(path) => format!("Saved replay to {}", path.display()),
+        Err(e) => format!("Could not save replay: {}", e),
+      };
+      self.notify(notice);
+    }
+    if self.state == GameState::Playing && self.bindings.is_released(Action::Pause) {
+      self.state = GameState::Paused;
+    }
+
+    self.draw_level(texs);
+  }
+
+  fn paused(&mut self, texs: &Textures) {
+    self.draw_level(texs);
+    screens::dim();
+    let resume = format!("{}: resume", self.bindings.describe(Action::Pause));
+    let quit = format!(
+      "{}: quit to level select",
+      self.bindings.describe(Action::Quit)
+    );
+    screens::message("Paused", &[&resume, &quit]);
+
+    if self.bindings.is_released(Action::Pause) {
+      self.state = GameState::Playing;
+    } else if self.bindings.is_released(Action::Quit) {
+      self.state = GameState::LevelSelect;
+    }
+  }
+
+  fn won(&mut self, texs: &Textures) {
+    self.draw_level(texs);
+    screens::dim();
+
+    //One item per campaign branch, then the way back to level select.
+    let next = self.levels.next_levels(self.sim.wrld.get_lvl());
+    let mut items: Vec<MenuItem> = next
+      .iter()
+      .map(|lvl| MenuItem {
+        label: format!("Next: {}", lvl.title),
+        enabled: self.is_unlocked(lvl),
+      })
+      .collect();
+    items.push(MenuItem {
+      label: "Level select".to_owned(),
+      enabled: true,
+    });
+    let next: Vec<usize> = next.iter().map(|lvl| lvl.index as usize).collect();
+
+    let title = if next.is_empty() {
+      "Campaign complete!"
+    } else {
+      "Victory!"
+    };
+    match screens::menu(title, &items) {
+      Some(i) if i < next.len() => self.start_level(next[i]),
+      Some(_) => self.state = GameState::LevelSelect,
+      None
+        if !next.is_empty() && items[0].enabled && self.bindings.is_released(Action::Confirm) =>
+      {
+        self.start_level(next[0])
+      }
+      None if self.bindings.is_released(Action::Back) => self.state = GameState::LevelSelect,
+      None => {}
+    }
+  }
+
+  fn lost(&mut self, texs: &Textures) {
+    self.draw_level(texs);
+    screens::dim();
+
+    let items = [
+      MenuItem {
+        label: "Retry".to_owned(),
+        enabled: true,
+      },
+      MenuItem {
+        label: "Level select".to_owned(),
+        enabled: true,
+      },
+    ];
+    let current = self.sim.wrld.get_lvl().index as usize;
+    match screens::menu("Defeat", &items) {
+      Some(0) => self.start_level(current),
+      Some(_) => self.state = GameState::LevelSelect,
+      None if self.bindings.is_released(Action::Confirm) => self.start_level(current),
+      None if self.bindings.is_released(Action::Back) => self.state = GameState::LevelSelect,
+      None => {}
+    }
+  }
+}
+
+/** Level select entry, with the best scores once the level is completed. */
+fn level_label(lvl: &Level, profile: &SaveProfile) -> String {
+  let title = format!("{}. {}", lvl.index + 1, lvl.title);
+  match profile.get(&lvl.name) {
+    Some(p) if p.completed => {
+      let secs = (p.fastest_ticks.unwrap_or(0) as f32 * TICK).round() as u64;
+      format!(
+        "{}  (done - health {}, {} towers, {}:{:02})",
+        title,
+        p.best_health.unwrap_or(0),
+        p.fewest_towers.unwrap_or(0),
+        secs / 60,
+        secs % 60
+      )
+    }
+    _ => title,
+  }
+}
+
+/** Saves `replay` under the replay directory. Returns where it went. */
+fn save_replay(replay: &Replay) -> Result<PathBuf, String> {
+  let secs = SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|d| d.as_secs())
+    .unwrap_or(0);
+  let path: PathBuf = [REPLAY_PATH, &format!("{}-{}.replay", replay.level, secs)]
+    .iter()
+    .collect();
+  replay
+    .save(&path)
+    .map(|()| path.clone())
+    .map_err(|e| format!("{}: {}", path.display(), e))
+}
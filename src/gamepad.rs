@@ -0,0 +1,164 @@
+#[cfg(feature = "gamepad")]
+use gilrs::{EventType, Gilrs};
+use std::{cell::RefCell, collections::HashSet};
+
+/**
+ * Gamepad buttons by where they sit on the pad, named like gilrs' `Button`. Builds without the
+ * `gamepad` feature still know them, so bindings files name the same buttons either way.
+ */
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum Button {
+  South,
+  East,
+  North,
+  West,
+  C,
+  Z,
+  LeftTrigger,
+  LeftTrigger2,
+  RightTrigger,
+  RightTrigger2,
+  Select,
+  Start,
+  Mode,
+  LeftThumb,
+  RightThumb,
+  DPadUp,
+  DPadDown,
+  DPadLeft,
+  DPadRight,
+}
+
+#[cfg(feature = "gamepad")]
+impl Button {
+  fn from_gilrs(button: gilrs::Button) -> Option<Button> {
+    use gilrs::Button as G;
+    Some(match button {
+      G::South => Button::South,
+      G::East => Button::East,
+      G::North => Button::North,
+      G::West => Button::West,
+      G::C => Button::C,
+      G::Z => Button::Z,
+      G::LeftTrigger => Button::LeftTrigger,
+      G::LeftTrigger2 => Button::LeftTrigger2,
+      G::RightTrigger => Button::RightTrigger,
+      G::RightTrigger2 => Button::RightTrigger2,
+      G::Select => Button::Select,
+      G::Start => Button::Start,
+      G::Mode => Button::Mode,
+      G::LeftThumb => Button::LeftThumb,
+      G::RightThumb => Button::RightThumb,
+      G::DPadUp => Button::DPadUp,
+      G::DPadDown => Button::DPadDown,
+      G::DPadLeft => Button::DPadLeft,
+      G::DPadRight => Button::DPadRight,
+      G::Unknown => return None,
+    })
+  }
+}
+
+/**
+ * Gamepad buttons, read through gilrs since macroquad 0.3 has no gamepad input. Works like
+ * macroquad's keyboard functions: `update` once a frame, then ask about buttons. Buttons of all
+ * connected pads count as one. Without the `gamepad` feature no button is ever down.
+ */
+struct Pads {
+  /** Why the platform offers no gamepad support, if it doesn't. */
+  #[cfg(feature = "gamepad")]
+  gilrs: Result<Gilrs, String>,
+  down: HashSet<Button>,
+  pressed: HashSet<Button>,
+  released: HashSet<Button>,
+}
+
+impl Pads {
+  fn new() -> Pads {
+    Pads {
+      #[cfg(feature = "gamepad")]
+      gilrs: Gilrs::new().map_err(|e| e.to_string()),
+      down: HashSet::new(),
+      pressed: HashSet::new(),
+      released: HashSet::new(),
+    }
+  }
+
+  fn update(&mut self) {
+    self.pressed.clear();
+    self.released.clear();
+    #[cfg(feature = "gamepad")]
+    self.poll();
+  }
+
+  #[cfg(feature = "gamepad")]
+  fn poll(&mut self) {
+    let gilrs = match &mut self.gilrs {
+      Ok(gilrs) => gilrs,
+      Err(_) => return,
+    };
+    while let Some(event) = gilrs.next_event() {
+      match event.event {
+        EventType::ButtonPressed(button, _) => {
+          if let Some(button) = Button::from_gilrs(button) {
+            self.down.insert(button);
+            self.pressed.insert(button);
+          }
+        }
+        EventType::ButtonReleased(button, _) => {
+          if let Some(button) = Button::from_gilrs(button) {
+            self.down.remove(&button);
+            self.released.insert(button);
+          }
+        }
+        //Buttons held on a pad that goes away would never be released.
+        EventType::Disconnected => self.down.clear(),
+        _ => {}
+      }
+    }
+  }
+}
+
+thread_local! {
+  static PADS: RefCell<Pads> = RefCell::new(Pads::new());
+}
+
+/**
+ * Starts gamepad input, failing if the platform offers none. Builds without the `gamepad` feature
+ * never fail, they just never see a button.
+ */
+#[cfg(feature = "gamepad")]
+pub fn init() -> Result<(), String> {
+  PADS.with(|pads| {
+    pads
+      .borrow()
+      .gilrs
+      .as_ref()
+      .map(|_| ())
+      .map_err(|e| e.clone())
+  })
+}
+#[cfg(not(feature = "gamepad"))]
+pub fn init() -> Result<(), String> {
+  Ok(())
+}
+
+/** Reads the gamepad events since the last call. Call once per rendered frame. */
+pub fn update() {
+  PADS.with(|pads| pads.borrow_mut().update());
+}
+
+pub fn is_button_down(button: Button) -> bool {
+  PADS.with(|pads| pads.borrow().down.contains(&button))
+}
+/** Went down this frame. */
+pub fn is_button_pressed(button: Button) -> bool {
+  PADS.with(|pads| pads.borrow().pressed.contains(&button))
+}
+/** Went up this frame. */
+pub fn is_button_released(button: Button) -> bool {
+  PADS.with(|pads| pads.borrow().released.contains(&button))
+}
+/** A button that went down this frame, if any. */
+pub fn get_last_button_pressed() -> Option<Button> {
+  PADS.with(|pads| pads.borrow().pressed.iter().next().copied())
+}
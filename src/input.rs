@@ -0,0 +1,92 @@
+use crate::{
+  bindings::{Action, Bindings},
+  tower::TowerType,
+  tower::Towers,
+  ui,
+  view::View,
+  wrld::World,
+};
+
+/** A player action, the only way input reaches the simulation. */
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Command {
+  SelectTower(TowerType),
+  Deselect,
+  PlaceTower((usize, usize)),
+  UpgradeTower((usize, usize)),
+  /** Picks the player's tower on that tile up again, if the level allows refunds. */
+  RemoveTower((usize, usize)),
+  SpeedUp,
+  SpeedDown,
+  Spawn,
+}
+
+/** Translates this frame's actions into commands. */
+pub fn poll_commands(
+  wrld: &World,
+  towers: &Towers,
+  view: &mut View,
+  bindings: &Bindings,
+) -> Vec<Command> {
+  let mut commands = Vec::new();
+
+  let selections = [
+    (Action::SelectBlockerUp, TowerType::BlockerUp),
+    (Action::SelectBlockerDown, TowerType::BlockerDown),
+    (Action::SelectLava, TowerType::Lava),
+    (Action::SelectGeyser, TowerType::Geyser),
+    (Action::SelectCollector, TowerType::Collector),
+  ];
+  for (action, kind) in selections {
+    if bindings.is_pressed(action) {
+      commands.push(Command::SelectTower(kind));
+    }
+  }
+  if bindings.is_pressed(Action::Deselect) {
+    commands.push(Command::Deselect);
+    view.selected_tower = None;
+  }
+
+  if bindings.is_released(Action::SpeedUp) {
+    commands.push(Command::SpeedUp);
+  }
+  if bindings.is_released(Action::SpeedDown) {
+    commands.push(Command::SpeedDown);
+  }
+
+  if bindings.is_released(Action::Spawn) {
+    commands.push(Command::Spawn);
+  }
+
+  if bindings.is_pressed(Action::Place) {
+    if let Some(twr) = ui::button_at(wrld, towers, view) {
+      commands.push(Command::SelectTower(twr));
+      view.selected_tower = None;
+    } else if wrld.selected_tower_type.is_none() {
+      //Clicking a built tower selects it for upgrades, clicking anywhere else lets go.
+      view.selected_tower = view
+        .get_mouse_grid()
+        .filter(|g| towers.get_tower(wrld, *g).is_some());
+    }
+  }
+  if bindings.is_released(Action::Upgrade) {
+    if let Some(grid_pos) = view.selected_tower {
+      commands.push(Command::UpgradeTower(grid_pos));
+    }
+  }
+
+  //The same button may drag the view, a drag is not a click.
+  if bindings.is_released(Action::Remove) && !view.was_dragged() {
+    if let Some(grid_pos) = view.get_mouse_grid() {
+      commands.push(Command::RemoveTower(grid_pos));
+    }
+  }
+
+  if bindings.is_released(Action::Place) && wrld.selected_tower_type.is_some() {
+    if let Some(grid_pos) = view.get_mouse_grid() {
+      commands.push(Command::PlaceTower(grid_pos));
+    }
+  }
+
+  commands
+}
@@ -1,11 +1,14 @@
 use enum_map::EnumMap;
+use rand::{rngs::StdRng, Rng, SeedableRng};
 use serde::Deserialize;
+use std::collections::HashSet;
 
 use crate::{
-  emath::grid_pos_to_pos,
+  emath::{grid_pos_to_pos, pos_to_grid_pos},
+  enemy::EnemyTable,
   spawner::{SpawnSpan, SpawnSpanSerialized, Spawner},
   tile::{BaseTile, TileType},
-  tower::TowerType,
+  tower::{TowerTable, TowerType},
 };
 
 #[derive(Deserialize, Debug, Clone)]
@@ -14,30 +17,128 @@ pub struct TowerSettings {
   pub block_down: Option<isize>,
   pub block_up: Option<isize>,
   pub lava: Option<isize>,
+  pub geyser: Option<isize>,
+}
+
+/**
+ * The `[economy]` table. A level with one pays for towers from a budget, on top of any supply
+ * caps it sets; a level without one only has its supply.
+ */
+#[derive(Deserialize, Debug, Clone)]
+pub struct EconomySettings {
+  pub budget: usize,
+  /** Paid for every enemy killed or caught. */
+  pub reward: Option<usize>,
+  /** `[economy.costs]`, kinds left out cost what their definition says. */
+  pub costs: Option<TowerSettings>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
 pub struct LevelConfig {
   pub enemies: Vec<SpawnSpanSerialized>,
   pub health: Option<usize>,
+  /** Fixes the level's random choices. Defaults to a hash of the level file name. */
+  pub seed: Option<u64>,
+  /** Whether built towers can be picked up again for their supply. Defaults to true. */
+  pub refunds: Option<bool>,
+  pub economy: Option<EconomySettings>,
+  /** Tower upgrades the player may make in levels without an economy. Defaults to none. */
+  pub upgrades: Option<usize>,
   pub towers: TowerSettings,
 }
 
+#[derive(Deserialize, Debug, Clone)]
+pub struct CampaignEntry {
+  pub file: String,
+  pub name: Option<String>,
+  #[serde(default)]
+  pub requires: Vec<String>,
+  pub next: Option<Vec<String>>,
+}
+
+/** The campaign manifest, `assets/levels/campaign.toml`. */
+#[derive(Deserialize, Debug, Clone)]
+pub struct Campaign {
+  pub levels: Vec<CampaignEntry>,
+}
+
+/** Default reward for an enemy, in levels with an economy. */
+const DEFAULT_REWARD: usize = 5;
+
+#[derive(Debug, Clone)]
+pub struct Economy {
+  pub budget: usize,
+  pub reward: usize,
+  pub costs: EnumMap<TowerType, usize>,
+}
+
+impl Economy {
+  fn new(settings: &EconomySettings, table: &TowerTable) -> Economy {
+    let cost = |setting: Option<&TowerSettings>, kind: TowerType| {
+      let value = setting.and_then(|costs| match kind {
+        TowerType::Collector => costs.collector,
+        TowerType::BlockerDown => costs.block_down,
+        TowerType::BlockerUp => costs.block_up,
+        TowerType::Lava => costs.lava,
+        TowerType::Geyser => costs.geyser,
+      });
+      as_usize(value, table.build_cost(kind))
+    };
+    let costs = settings.costs.as_ref();
+    Economy {
+      budget: settings.budget,
+      reward: settings.reward.unwrap_or(DEFAULT_REWARD),
+      costs: enum_map! { kind => cost(costs, kind) },
+    }
+  }
+}
+
 #[derive(Clone)]
 pub struct Level {
+  /** Position in the campaign. */
   pub index: u8,
+  /** File name without extension, identifies the level. */
+  pub name: String,
+  /** Display name. */
+  pub title: String,
+  /** Levels that must be completed before this one unlocks. */
+  pub requires: Vec<String>,
+  /** Levels offered after a win. */
+  pub next: Vec<String>,
   pub width: usize,
   pub height: usize,
   pub tiles: Vec<BaseTile>,
   pub health: usize,
+  pub seed: u64,
+  pub refunds: bool,
+  /** Set when towers cost money. */
+  pub economy: Option<Economy>,
+  pub upgrades: usize,
+  /** Definitions of every tower kind, the same for the whole campaign. */
+  pub tower_table: TowerTable,
+  /** Stats of every enemy type, the same for the whole campaign. */
+  pub enemy_table: EnemyTable,
   pub config: LevelConfig,
   pub twr_supply: EnumMap<TowerType, usize>,
   pub spawner: Spawner,
 }
 
 impl Level {
-  pub fn new(width: usize, tiles: Vec<BaseTile>, config: LevelConfig) -> Level {
-    Level {
+  pub fn new(
+    name: &str,
+    width: usize,
+    tiles: Vec<BaseTile>,
+    config: LevelConfig,
+    seed: u64,
+    tower_table: TowerTable,
+    enemy_table: EnemyTable,
+  ) -> Level {
+    let mut lvl = Level {
+      name: name.to_owned(),
+      title: name.to_owned(),
+      requires: Vec::new(),
+      next: Vec::new(),
+      seed,
       index: 0,
       width,
       height: tiles.len() / width,
@@ -45,36 +146,71 @@ impl Level {
       twr_supply: Level::calc_tower_supply(&config),
       spawner: Spawner::new(get_valid_spawn_spans(&config.enemies)),
       health: config.health.unwrap_or(100),
+      refunds: config.refunds.unwrap_or(true),
+      economy: config
+        .economy
+        .as_ref()
+        .map(|e| Economy::new(e, &tower_table)),
+      upgrades: config.upgrades.unwrap_or(0),
+      tower_table,
+      enemy_table,
       config,
+    };
+    lvl.reseed(seed);
+    lvl
+  }
+  /** Sets the seed and rolls the background tile variants from it again. */
+  pub fn reseed(&mut self, seed: u64) {
+    self.seed = seed;
+    let mut rng = StdRng::seed_from_u64(seed);
+    for tile in self.tiles.iter_mut().filter(|t| t.kind == TileType::Empty) {
+      tile.variant = match rng.gen_range(0..7) {
+        1 | 2 => 1,
+        3 | 4 => 2,
+        5 => 3,
+        _ => 0,
+      };
     }
   }
+  /** Loaded levels are checked to have at least one spawn. */
   pub fn find_spawns(&self) -> Vec<(usize, usize)> {
-    let spawns: Vec<(usize, usize)> = self
+    self
       .tiles
       .iter()
       .filter_map(|t| match t.kind {
         TileType::Spawn => Some(grid_pos_to_pos(&t.grid_pos)),
         _ => None,
       })
-      .collect();
-
-    if spawns.len() == 0 {
-      panic!("Invalid level: {}, could not find any spawns.", self.index);
-    }
-
-    spawns
+      .collect()
+  }
+  /** Grid position of the goal tile closest to the level pixel `pos`, if the level has one. */
+  pub fn nearest_goal(&self, pos: (usize, usize)) -> Option<(usize, usize)> {
+    let grid_pos = pos_to_grid_pos(&pos);
+    self
+      .tiles
+      .iter()
+      .filter(|t| t.kind == TileType::Goal)
+      .map(|t| t.grid_pos)
+      .min_by_key(|goal| goal.0.abs_diff(grid_pos.0) + goal.1.abs_diff(grid_pos.1))
   }
+  /** With an economy the budget limits towers, so kinds without a supply are unlimited. */
   fn calc_tower_supply(cfg: &LevelConfig) -> EnumMap<TowerType, usize> {
+    let default = if cfg.economy.is_some() { usize::MAX } else { 0 };
     enum_map! {
-      TowerType::Collector => as_usize(cfg.towers.collector, 0),
-      TowerType::BlockerDown => as_usize(cfg.towers.block_down, 0),
-      TowerType::BlockerUp => as_usize(cfg.towers.block_up, 0),
-      TowerType::Lava => as_usize(cfg.towers.lava, 0),
+      TowerType::Collector => as_usize(cfg.towers.collector, default),
+      TowerType::BlockerDown => as_usize(cfg.towers.block_down, default),
+      TowerType::BlockerUp => as_usize(cfg.towers.block_up, default),
+      TowerType::Lava => as_usize(cfg.towers.lava, default),
+      TowerType::Geyser => as_usize(cfg.towers.geyser, default),
     }
   }
   pub fn get_tower_supply(&self, kind: &TowerType) -> usize {
     self.twr_supply[*kind]
   }
+  /** Zero without an economy. */
+  pub fn get_tower_cost(&self, kind: &TowerType) -> usize {
+    self.economy.as_ref().map_or(0, |e| e.costs[*kind])
+  }
 }
 
 pub struct Levels {
@@ -82,20 +218,65 @@ pub struct Levels {
 }
 
 impl Levels {
-  pub fn new(levels: Vec<Level>) -> Levels {
-    Levels { levels }
+  /** Orders and links `levels` as listed in the campaign. Levels missing from it are dropped. */
+  pub fn new(campaign: &Campaign, mut levels: Vec<Level>) -> Levels {
+    let ordered = campaign
+      .levels
+      .iter()
+      .enumerate()
+      .filter_map(|(i, entry)| {
+        let pos = levels.iter().position(|l| l.name == entry.file)?;
+        let mut lvl = levels.swap_remove(pos);
+        lvl.index = i as u8;
+        lvl.title = entry.name.clone().unwrap_or_else(|| entry.file.clone());
+        lvl.requires = entry.requires.clone();
+        lvl.next = match &entry.next {
+          Some(next) => next.clone(),
+          None => campaign
+            .levels
+            .get(i + 1)
+            .map(|e| vec![e.file.clone()])
+            .unwrap_or_default(),
+        };
+        Some(lvl)
+      })
+      .collect();
+    Levels { levels: ordered }
+  }
+  pub fn len(&self) -> usize {
+    self.levels.len()
+  }
+  pub fn is_empty(&self) -> bool {
+    self.levels.is_empty()
   }
-  pub fn get_level(&self, index: usize) -> Level {
-    self.levels.get(index).unwrap_or(&self.levels[0]).clone()
+  pub fn iter(&self) -> impl Iterator<Item = &Level> {
+    self.levels.iter()
+  }
+  pub fn get_level(&self, index: usize) -> Option<Level> {
+    self.levels.get(index).cloned()
+  }
+  pub fn find_level(&self, name: &str) -> Option<Level> {
+    self.levels.iter().find(|l| l.name == name).cloned()
+  }
+  /** A level unlocks once every level it requires is completed. */
+  pub fn is_unlocked(&self, lvl: &Level, completed: &HashSet<String>) -> bool {
+    lvl.requires.iter().all(|r| completed.contains(r))
+  }
+  /** The levels offered after winning `lvl`, in manifest order of its `next` list. */
+  pub fn next_levels(&self, lvl: &Level) -> Vec<&Level> {
+    lvl
+      .next
+      .iter()
+      .filter_map(|name| self.levels.iter().find(|l| &l.name == name))
+      .collect()
   }
 }
 
-fn get_valid_spawn_spans(span_data: &Vec<SpawnSpanSerialized>) -> Vec<SpawnSpan> {
-  let mut spans = span_data.clone();
-  spans.retain(|s| s.count > 0 && s.time > 0.0);
-  spans
-    .iter_mut()
-    .map(|s| SpawnSpan::new(s.time, s.count as usize))
+fn get_valid_spawn_spans(span_data: &[SpawnSpanSerialized]) -> Vec<SpawnSpan> {
+  span_data
+    .iter()
+    .filter(|s| s.count > 0 && s.time > 0.0)
+    .map(|s| SpawnSpan::new(s.time, s.count as usize, &s.kind))
     .collect()
 }
 
@@ -107,3 +288,74 @@ fn as_usize(val: Option<isize>, default: usize) -> usize {
   }
   default
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::loading::{read_enemy_table, read_level, read_tower_table};
+
+  fn read(name: &str) -> Level {
+    read_level(
+      name,
+      &read_tower_table().unwrap(),
+      &read_enemy_table().unwrap(),
+    )
+    .unwrap()
+  }
+
+  #[test]
+  fn campaign_orders_and_links_levels() {
+    let campaign: Campaign = toml::from_str(
+      r#"
+        [[levels]]
+        file = "level2"
+        name = "Second"
+        next = ["level2", "level1"]
+        [[levels]]
+        file = "level1"
+        requires = ["level2"]
+      "#,
+    )
+    .unwrap();
+    let lvls = Levels::new(&campaign, vec![read("level1"), read("level2")]);
+
+    let first = lvls.get_level(0).unwrap();
+    assert_eq!(first.name, "level2");
+    assert_eq!(first.title, "Second");
+    let next: Vec<&str> = lvls
+      .next_levels(&first)
+      .iter()
+      .map(|l| l.name.as_str())
+      .collect();
+    assert_eq!(next, ["level2", "level1"]);
+
+    let last = lvls.get_level(1).unwrap();
+    assert_eq!(last.title, "level1");
+    assert!(lvls.next_levels(&last).is_empty());
+    assert!(lvls.get_level(2).is_none());
+
+    let mut completed = HashSet::new();
+    assert!(!lvls.is_unlocked(&last, &completed));
+    completed.insert("level2".to_owned());
+    assert!(lvls.is_unlocked(&last, &completed));
+  }
+
+  #[test]
+  fn reseeding_rolls_tiles_like_loading() {
+    let lvl = read("level1");
+    let seeded = Level::new(
+      &lvl.name,
+      lvl.width,
+      lvl.tiles.clone(),
+      lvl.config.clone(),
+      lvl.seed ^ 1,
+      lvl.tower_table.clone(),
+      lvl.enemy_table.clone(),
+    );
+    let mut reseeded = lvl.clone();
+    reseeded.reseed(lvl.seed ^ 1);
+    let variants = |lvl: &Level| lvl.tiles.iter().map(|t| t.variant).collect::<Vec<_>>();
+    assert_eq!(variants(&reseeded), variants(&seeded));
+    assert_ne!(variants(&reseeded), variants(&lvl));
+  }
+}
@@ -1,271 +1,694 @@
 use enum_map::EnumMap;
-use futures::future::join_all;
-use macroquad::{
-  rand::RandomRange,
-  texture::{load_image, load_texture, Texture2D},
+use image::{ImageFormat, RgbaImage};
+use macroquad::texture::Texture2D;
+use serde::Deserialize;
+use std::{
+  collections::HashMap,
+  fmt::{self, Display},
+  fs::{read, read_to_string},
+  io,
 };
-use std::fs::read_to_string;
-use toml::{self, de::Error};
 
 use crate::{
+  atlas::{self, Frame},
   emath::i_to_xy,
-  level::{Level, LevelConfig, Levels},
+  enemy::{EnemyDef, EnemyTable},
+  level::{Campaign, Level, LevelConfig, Levels},
   tile::{BaseTile, TileType},
-  tower::TowerType,
+  tower::{TowerDef, TowerTable, TowerType},
 };
 
 static ASSET_PATH: &str = "assets";
 static TEXTURE_PATH: &str = "assets/textures";
+static SPRITE_FILE: &str = "sprites.toml";
+static TOWER_FILE: &str = "assets/towers.toml";
+static ENEMY_FILE: &str = "assets/enemies.toml";
+/** Drawn for sprite ids the manifest does not list. */
+pub static MISSING_SPRITE: &str = "missing";
+
+/** Why the game data could not be loaded. Shown to the player instead of starting the game. */
+#[derive(Debug)]
+pub enum LoadError {
+  /** A file could not be read at all. */
+  File {
+    path: String,
+    message: String,
+  },
+  /** An image is not a valid PNG. */
+  Image {
+    path: String,
+    message: String,
+  },
+  /** A level pixel whose colour is not a known tile. */
+  UnknownColour {
+    level: String,
+    x: usize,
+    y: usize,
+    rgba: [u8; 4],
+  },
+  /** A TOML file that does not parse, the message names the offending key. */
+  Toml {
+    path: String,
+    error: toml::de::Error,
+  },
+  NoSpawn {
+    level: String,
+  },
+  /** The campaign manifest lists no levels. */
+  EmptyCampaign,
+  /** A campaign entry refers to a level that is not in the campaign. */
+  UnknownLevel {
+    level: String,
+    reference: String,
+  },
+  /** A sprite manifest entry that can not be used. */
+  Sprite {
+    id: String,
+    message: String,
+  },
+  Replay {
+    message: String,
+  },
+  /** The tower table leaves out a kind or gives it no tiers. */
+  Tower {
+    kind: String,
+    message: String,
+  },
+  /** An enemy type whose stats can not be used. */
+  Enemy {
+    kind: String,
+    message: String,
+  },
+  /** A spawn span names an enemy type the enemy table does not have. */
+  UnknownEnemy {
+    level: String,
+    kind: String,
+  },
+}
+
+impl Display for LoadError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      LoadError::File { path, message } => write!(f, "Could not read {}: {}", path, message),
+      LoadError::Image { path, message } => write!(f, "Invalid image {}: {}", path, message),
+      LoadError::UnknownColour { level, x, y, rgba } => write!(
+        f,
+        "Level {}: pixel ({}, {}) has unknown tile colour {:?}.",
+        level, x, y, rgba
+      ),
+      LoadError::Toml { path, error } => write!(f, "Invalid {}: {}", path, error),
+      LoadError::NoSpawn { level } => write!(f, "Level {} has no spawn tile.", level),
+      LoadError::EmptyCampaign => write!(f, "The campaign lists no levels."),
+      LoadError::UnknownLevel { level, reference } => write!(
+        f,
+        "Campaign level {} refers to unknown level {}.",
+        level, reference
+      ),
+      LoadError::Sprite { id, message } => write!(f, "Sprite {}: {}.", id, message),
+      LoadError::Replay { message } => write!(f, "Invalid replay {}", message),
+      LoadError::Tower { kind, message } => write!(f, "Tower {}: {}.", kind, message),
+      LoadError::Enemy { kind, message } => write!(f, "Enemy {}: {}.", kind, message),
+      LoadError::UnknownEnemy { level, kind } => {
+        write!(f, "Level {} spawns unknown enemy {}.", level, kind)
+      }
+    }
+  }
+}
+
+impl std::error::Error for LoadError {}
+
+fn file_error(path: &str, error: io::Error) -> LoadError {
+  LoadError::File {
+    path: path.to_owned(),
+    message: error.to_string(),
+  }
+}
+/** One manifest entry of `assets/textures/sprites.toml`. */
+#[derive(Deserialize, Debug, Clone)]
+pub struct SpriteDef {
+  pub file: Option<String>,
+  #[serde(default)]
+  pub frames: Vec<String>,
+  /** Seconds each frame is shown. Still sprites have none. */
+  pub frame_time: Option<f32>,
+  #[serde(default = "default_looping")]
+  pub looping: bool,
+  /** Draws the frames upside down, so one image serves towers hanging down and standing up. */
+  #[serde(default)]
+  pub flip_y: bool,
+}
+
+fn default_looping() -> bool {
+  true
+}
+
+impl SpriteDef {
+  /** Texture files of the sprite in frame order. */
+  pub fn files(&self) -> Vec<&str> {
+    self
+      .file
+      .iter()
+      .chain(self.frames.iter())
+      .map(String::as_str)
+      .collect()
+  }
+}
+
+pub struct Sprite {
+  pub frames: Vec<Frame>,
+  pub frame_time: Option<f32>,
+  pub looping: bool,
+}
+
+impl Sprite {
+  /** The frame shown `time` seconds into the animation. */
+  pub fn frame(&self, time: f32) -> Frame {
+    self.frames[frame_index(self.frames.len(), self.frame_time, self.looping, time)]
+  }
+}
+
+fn frame_index(count: usize, frame_time: Option<f32>, looping: bool, time: f32) -> usize {
+  let i = match frame_time {
+    Some(frame_time) if frame_time > 0.0 => (time.max(0.0) / frame_time) as usize,
+    _ => 0,
+  };
+  if looping {
+    i % count
+  } else {
+    i.min(count - 1)
+  }
+}
+
+/** Every sprite of the game by id, as listed in the sprite manifest. */
 pub struct Textures {
-  pub bg_0: Texture2D,
-  pub bg_1: Texture2D,
-  pub bg_2: Texture2D,
-  pub bg_3: Texture2D,
-  pub blocker_down: Texture2D,
-  pub blocker_up: Texture2D,
-  pub border_bottom_left: Texture2D,
-  pub border_bottom_right: Texture2D,
-  pub border_bottom: Texture2D,
-  pub border_left: Texture2D,
-  pub border_right: Texture2D,
-  pub border_top_left: Texture2D,
-  pub border_top_right: Texture2D,
-  pub border_top: Texture2D,
-  pub build_down: Texture2D,
-  pub build_up: Texture2D,
-  pub empty: Texture2D,
-  pub enemy: Texture2D,
-  pub goal: Texture2D,
-  pub lava_drop: Texture2D,
-  pub lava_splash: Vec<Texture2D>,
-  pub spawn: Texture2D,
-  pub terrain_center: Texture2D,
-  pub terrain_down: Texture2D,
-  pub terrain_up: Texture2D,
-  pub tower_lava: Vec<Texture2D>,
-  pub turret_down: Texture2D,
-  pub turret_up: Texture2D,
-  pub tower_buttons: EnumMap<TowerType, ButtonTexs>,
+  /** Atlas sheets every frame is drawn from. */
+  pub sheets: Vec<Texture2D>,
+  sprites: HashMap<String, Sprite>,
+}
+
+impl Textures {
+  /** Unknown ids fall back to the `missing` sprite. */
+  pub fn sprite(&self, id: &str) -> &Sprite {
+    self
+      .sprites
+      .get(id)
+      .unwrap_or_else(|| &self.sprites[MISSING_SPRITE])
+  }
+  /** The first frame of a sprite. */
+  pub fn get(&self, id: &str) -> Frame {
+    self.sprite(id).frames[0]
+  }
+  /** The frame of an animated sprite `time` seconds in. */
+  pub fn frame(&self, id: &str, time: f32) -> Frame {
+    self.sprite(id).frame(time)
+  }
+  pub fn tile(&self, kind: &TileType, variant: u8) -> Frame {
+    self.get(tile_sprite(kind, variant))
+  }
 }
 
-#[derive(Debug, Clone, Copy)]
-pub struct ButtonTexs {
-  pub normal: Texture2D,
-  pub highlighted: Texture2D,
-  pub selected: Texture2D,
+pub fn tile_sprite(kind: &TileType, variant: u8) -> &'static str {
+  match kind {
+    //Painted towers stand on plain terrain, the tower itself is drawn on top.
+    TileType::BlockerDown | TileType::TurretDown => "terrain_down",
+    TileType::BlockerUp | TileType::TurretUp => "terrain_up",
+    TileType::BorderBottom => "border_bottom",
+    TileType::BorderBottomLeft => "border_bottom_left",
+    TileType::BorderBottomRight => "border_bottom_right",
+    TileType::BorderLeft => "border_left",
+    TileType::BorderRight => "border_right",
+    TileType::BorderTop => "border_top",
+    TileType::BorderTopLeft => "border_top_left",
+    TileType::BorderTopRight => "border_top_right",
+    TileType::BuildDown => "build_down",
+    TileType::BuildUp => "build_up",
+    TileType::Empty => match variant {
+      1 => "bg_1",
+      2 => "bg_2",
+      3 => "bg_3",
+      _ => "bg_0",
+    },
+    TileType::Goal => "goal",
+    TileType::Spawn => "spawn",
+    TileType::TerrainCenter => "terrain_center",
+    TileType::TerrainDown => "terrain_down",
+    TileType::TerrainUp => "terrain_up",
+  }
 }
 
 fn tex_path(name: &str) -> String {
   format!("{}/{}.png", TEXTURE_PATH, name)
 }
-fn tower_path(name: &str) -> String {
-  format!("{}/towers/{}.png", TEXTURE_PATH, name)
-}
 /** Name with .ext */
 fn level_path(name: &str) -> String {
   format!("{}/levels/{}", ASSET_PATH, name)
 }
 
-pub async fn load_textures() -> Textures {
-  // let mut map = ;
-  Textures {
-    bg_0: load_texture(&tex_path("bg_0")).await.unwrap(),
-    bg_1: load_texture(&tex_path("bg_1")).await.unwrap(),
-    bg_2: load_texture(&tex_path("bg_2")).await.unwrap(),
-    bg_3: load_texture(&tex_path("bg_3")).await.unwrap(),
-    border_bottom_left: load_texture(&tex_path("border_bottom_left")).await.unwrap(),
-    border_bottom_right: load_texture(&tex_path("border_bottom_right"))
-      .await
-      .unwrap(),
-    border_bottom: load_texture(&tex_path("border_bottom")).await.unwrap(),
-    border_left: load_texture(&tex_path("border_left")).await.unwrap(),
-    border_right: load_texture(&tex_path("border_right")).await.unwrap(),
-    border_top_left: load_texture(&tex_path("border_top_left")).await.unwrap(),
-    border_top_right: load_texture(&tex_path("border_top_right")).await.unwrap(),
-    border_top: load_texture(&tex_path("border_top")).await.unwrap(),
-    build_down: load_texture(&tex_path("build_down")).await.unwrap(),
-    build_up: load_texture(&tex_path("build_up")).await.unwrap(),
-    empty: load_texture(&tex_path("empty")).await.unwrap(),
-    goal: load_texture(&tex_path("goal")).await.unwrap(),
-    spawn: load_texture(&tex_path("hole1")).await.unwrap(),
-    terrain_center: load_texture(&tex_path("center")).await.unwrap(),
-    terrain_down: load_texture(&tex_path("down")).await.unwrap(),
-    terrain_up: load_texture(&tex_path("up")).await.unwrap(),
-    turret_down: load_texture(&tex_path("missing")).await.unwrap(),
-    turret_up: load_texture(&tex_path("missing")).await.unwrap(),
-
-    //BUILDABLE
-    blocker_down: load_texture(&tower_path("blocker_down")).await.unwrap(),
-    blocker_up: load_texture(&tower_path("blocker_up")).await.unwrap(),
-    tower_lava: vec![
-      load_texture(&tower_path("lava_0")).await.unwrap(),
-      load_texture(&tower_path("lava_1")).await.unwrap(),
-    ],
-
-    //Effects
-    lava_drop: load_texture(&tower_path("lava_drop1_5")).await.unwrap(),
-    lava_splash: vec![
-      load_texture(&tower_path("lava_drop1_6")).await.unwrap(),
-      load_texture(&tower_path("lava_drop1_7")).await.unwrap(),
-      load_texture(&tower_path("lava_drop1_8")).await.unwrap(),
-      load_texture(&tower_path("lava_drop1_9")).await.unwrap(),
-    ],
-
-    //Enemies
-    enemy: load_texture(&tex_path("enemy")).await.unwrap(),
-
-    //UI
-    tower_buttons: enum_map! {
-      TowerType::BlockerDown => ButtonTexs {
-        normal: load_texture(&tower_path("blocker_down_btn")).await.unwrap(),
-        highlighted: load_texture(&tower_path("blocker_down_btn_highlight")).await.unwrap(),
-        selected: load_texture(&tower_path("blocker_down_btn_selected")).await.unwrap()
-      },
-      TowerType::BlockerUp => ButtonTexs {
-        normal: load_texture(&tower_path("blocker_up_btn")).await.unwrap(),
-        highlighted: load_texture(&tower_path("blocker_up_btn_highlight")).await.unwrap(),
-        selected: load_texture(&tower_path("blocker_up_btn_selected")).await.unwrap()
-      },
-      TowerType::Collector => ButtonTexs {
-        normal: load_texture(&tower_path("collector_btn")).await.unwrap(),
-        highlighted: load_texture(&tower_path("collector_btn_highlight")).await.unwrap(),
-        selected: load_texture(&tower_path("collector_btn_selected")).await.unwrap()
-      },
-      TowerType::Lava => ButtonTexs {
-        normal: load_texture(&tower_path("lava_btn")).await.unwrap(),
-        highlighted: load_texture(&tower_path("lava_btn_highlight")).await.unwrap(),
-        selected: load_texture(&tower_path("lava_btn_selected")).await.unwrap()
-      },
-    },
+fn read_png(path: &str) -> Result<RgbaImage, LoadError> {
+  let bytes = read(path).map_err(|e| file_error(path, e))?;
+  image::load_from_memory_with_format(&bytes, ImageFormat::Png)
+    .map(|image| image.to_rgba8())
+    .map_err(|e| LoadError::Image {
+      path: path.to_owned(),
+      message: e.to_string(),
+    })
+}
+
+/** Loads every sprite in the manifest and packs their files into atlas sheets. */
+pub fn load_textures() -> Result<Textures, LoadError> {
+  let defs: HashMap<String, SpriteDef> = read_toml(&format!("{}/{}", TEXTURE_PATH, SPRITE_FILE))?;
+  if !defs.contains_key(MISSING_SPRITE) {
+    return Err(LoadError::Sprite {
+      id: MISSING_SPRITE.to_owned(),
+      message: "every manifest needs it as the fallback".to_owned(),
+    });
+  }
+  if let Some((id, _)) = defs.iter().find(|(_, def)| def.files().is_empty()) {
+    return Err(LoadError::Sprite {
+      id: id.clone(),
+      message: "needs a file or frames".to_owned(),
+    });
   }
+
+  //Each file once, sorted so the sheets come out the same on every run.
+  let mut files: Vec<&str> = defs.values().flat_map(|def| def.files()).collect();
+  files.sort_unstable();
+  files.dedup();
+  let images = files
+    .iter()
+    .map(|file| read_png(&tex_path(file)))
+    .collect::<Result<Vec<_>, _>>()?;
+  let (sheets, frames) = atlas::build(&images);
+  let file_frames: HashMap<&str, Frame> = files.into_iter().zip(frames).collect();
+
+  let sprites = defs
+    .iter()
+    .map(|(id, def)| {
+      let sprite = Sprite {
+        frames: def
+          .files()
+          .iter()
+          .map(|file| Frame {
+            flip_y: def.flip_y,
+            ..file_frames[file]
+          })
+          .collect(),
+        frame_time: def.frame_time,
+        looping: def.looping,
+      };
+      (id.clone(), sprite)
+    })
+    .collect();
+  Ok(Textures { sheets, sprites })
 }
 
-pub async fn load_levels(textures: &Textures) -> Levels {
-  let lvls = join_all(vec!["level1", "level2"].iter().map(|file_name| async {
-    let lvl_data = load_image(&level_path(&format!("{}.png", file_name.clone())))
-      .await
-      .unwrap();
+/** Loads every level listed in the campaign manifest. */
+pub fn load_levels() -> Result<Levels, LoadError> {
+  let campaign = read_campaign()?;
+  let tower_table = read_tower_table()?;
+  let enemy_table = read_enemy_table()?;
+  let lvls = campaign
+    .levels
+    .iter()
+    .map(|entry| read_level(&entry.file, &tower_table, &enemy_table))
+    .collect::<Result<_, _>>()?;
+  Ok(Levels::new(&campaign, lvls))
+}
 
-    println!("level w:{}, h:{}", lvl_data.width, lvl_data.height);
-    let mut i = 0;
-    let tiles = lvl_data
-      .get_image_data()
-      .iter()
-      .map(|p| {
-        let pos = i_to_xy(&(lvl_data.width as usize), &i);
-        let mut basetile = BaseTile {
-          kind: TileType::Empty,
-          texture: textures.empty,
-          grid_pos: pos,
-          index: i,
-          size: (1, 1),
-        };
-        match p {
-          [0, 0, 0, 255] => {
-            basetile.kind = TileType::BorderTopLeft;
-            basetile.texture = textures.border_top_left;
-          }
-          [30, 30, 30, 255] => {
-            basetile.kind = TileType::BorderTop;
-            basetile.texture = textures.border_top;
-          }
-          [60, 60, 60, 255] => {
-            basetile.kind = TileType::BorderTopRight;
-            basetile.texture = textures.border_top_right;
-          }
-          [90, 90, 90, 255] => {
-            basetile.kind = TileType::BorderRight;
-            basetile.texture = textures.border_right;
-          }
-          [120, 120, 120, 255] => {
-            basetile.kind = TileType::BorderBottomRight;
-            basetile.texture = textures.border_bottom_right;
-          }
-          [150, 150, 150, 255] => {
-            basetile.kind = TileType::BorderBottom;
-            basetile.texture = textures.border_bottom;
-          }
-          [180, 180, 180, 255] => {
-            basetile.kind = TileType::BorderBottomLeft;
-            basetile.texture = textures.border_bottom_left;
-          }
-          [210, 210, 210, 255] => {
-            basetile.kind = TileType::BorderLeft;
-            basetile.texture = textures.border_left;
-          }
-          [213, 0, 0, 255] => {
-            basetile.kind = TileType::Spawn;
-            basetile.texture = textures.spawn;
-          }
-          [113, 0, 0, 255] => {
-            basetile.kind = TileType::Goal;
-            basetile.texture = textures.goal;
-          }
-          [0, 200, 0, 255] => {
-            basetile.kind = TileType::TerrainUp;
-            basetile.texture = textures.terrain_up;
-          }
-          [0, 155, 0, 255] => {
-            basetile.kind = TileType::TerrainCenter;
-            basetile.texture = textures.terrain_center;
-          }
-          [0, 109, 0, 255] => {
-            basetile.kind = TileType::TerrainDown;
-            basetile.texture = textures.terrain_down;
-          }
-          [0, 0, 200, 255] => {
-            basetile.kind = TileType::BuildUp;
-            basetile.texture = textures.build_up;
-          }
-          [0, 0, 109, 255] => {
-            basetile.kind = TileType::BuildDown;
-            basetile.texture = textures.build_down;
-          }
-          _ => {
-            let r = RandomRange::gen_range(0, 7);
-            basetile.kind = TileType::Empty;
-            basetile.texture = match r {
-              1 => textures.bg_1,
-              2 => textures.bg_1,
-              3 => textures.bg_2,
-              4 => textures.bg_2,
-              5 => textures.bg_3,
-              _ => textures.bg_0,
-            };
-          }
-        };
-
-        i += 1;
-
-        basetile
+fn read_campaign() -> Result<Campaign, LoadError> {
+  let path = level_path("campaign.toml");
+  check_campaign(read_toml(&path)?)
+}
+
+/** Fails on a campaign without levels or with links to levels it does not list. */
+fn check_campaign(campaign: Campaign) -> Result<Campaign, LoadError> {
+  if campaign.levels.is_empty() {
+    return Err(LoadError::EmptyCampaign);
+  }
+
+  for entry in &campaign.levels {
+    let refs = entry.requires.iter().chain(entry.next.iter().flatten());
+    for name in refs {
+      if !campaign.levels.iter().any(|e| &e.file == name) {
+        return Err(LoadError::UnknownLevel {
+          level: entry.file.clone(),
+          reference: name.clone(),
+        });
+      }
+    }
+  }
+  Ok(campaign)
+}
+
+/** Reads a level image and its config from disk. Needs no window. */
+pub fn read_level(
+  file_name: &str,
+  tower_table: &TowerTable,
+  enemy_table: &EnemyTable,
+) -> Result<Level, LoadError> {
+  let path = level_path(&format!("{}.png", file_name));
+  let lvl_data = read_png(&path)?;
+  let pixels: Vec<[u8; 4]> = lvl_data.pixels().map(|p| p.0).collect();
+
+  let lvl_config: LevelConfig = read_toml(&level_path(&format!("{}.toml", file_name)))?;
+  let seed = lvl_config.seed.unwrap_or_else(|| name_seed(file_name));
+
+  parse_level(
+    file_name,
+    lvl_data.width() as usize,
+    &pixels,
+    lvl_config,
+    seed,
+    tower_table.clone(),
+    enemy_table.clone(),
+  )
+}
+
+/** The definition of every tower kind, `assets/towers.toml`. */
+pub fn read_tower_table() -> Result<TowerTable, LoadError> {
+  let mut table: HashMap<String, TowerDef> = read_toml(TOWER_FILE)?;
+  let defs: EnumMap<TowerType, Option<TowerDef>> =
+    enum_map! { kind => table.remove(TowerType::id(&kind)) };
+  let tower_error = |kind: &str, message: &str| LoadError::Tower {
+    kind: kind.to_owned(),
+    message: format!("{} {}", TOWER_FILE, message),
+  };
+  if let Some(unknown) = table.keys().next() {
+    return Err(tower_error(unknown, "names no such tower kind"));
+  }
+  if let Some((kind, _)) = defs.iter().find(|(_, def)| def.is_none()) {
+    return Err(tower_error(kind.id(), "has no definition"));
+  }
+  TowerTable::new(defs.map(|_, def| def.unwrap()))
+    .map_err(|(kind, message)| tower_error(kind.id(), &format!("gives it {}", message)))
+}
+
+/** The stats of every enemy type, `assets/enemies.toml`. */
+pub fn read_enemy_table() -> Result<EnemyTable, LoadError> {
+  let defs: HashMap<String, EnemyDef> = read_toml(ENEMY_FILE)?;
+  EnemyTable::new(defs).map_err(|(kind, message)| LoadError::Enemy {
+    kind,
+    message: format!("{} gives it {}", ENEMY_FILE, message),
+  })
+}
+
+/** FNV-1a hash of the level name, stable across builds and platforms. */
+fn name_seed(name: &str) -> u64 {
+  name.bytes().fold(0xcbf29ce484222325, |hash, b| {
+    (hash ^ b as u64).wrapping_mul(0x100000001b3)
+  })
+}
+
+/**
+ * Builds a level from raw RGBA pixels, one pixel per tile. White or transparent pixels are empty.
+ * Build tiles are blue, `(0, 0, 200)` up and `(0, 0, 109)` down; towers placed by the level use
+ * the same brightness in yellow for blockers and magenta for turrets.
+ */
+pub fn parse_level(
+  name: &str,
+  width: usize,
+  pixels: &[[u8; 4]],
+  config: LevelConfig,
+  seed: u64,
+  tower_table: TowerTable,
+  enemy_table: EnemyTable,
+) -> Result<Level, LoadError> {
+  if let Some(span) = config
+    .enemies
+    .iter()
+    .find(|span| enemy_table.get(&span.kind).is_none())
+  {
+    return Err(LoadError::UnknownEnemy {
+      level: name.to_owned(),
+      kind: span.kind.clone(),
+    });
+  }
+
+  let tiles = pixels
+    .iter()
+    .enumerate()
+    .map(|(i, p)| {
+      let grid_pos = i_to_xy(&width, &i);
+      let (kind, variant) = match p {
+        [0, 0, 0, 255] => (TileType::BorderTopLeft, 0),
+        [30, 30, 30, 255] => (TileType::BorderTop, 0),
+        [60, 60, 60, 255] => (TileType::BorderTopRight, 0),
+        [90, 90, 90, 255] => (TileType::BorderRight, 0),
+        [120, 120, 120, 255] => (TileType::BorderBottomRight, 0),
+        [150, 150, 150, 255] => (TileType::BorderBottom, 0),
+        [180, 180, 180, 255] => (TileType::BorderBottomLeft, 0),
+        [210, 210, 210, 255] => (TileType::BorderLeft, 0),
+        [213, 0, 0, 255] => (TileType::Spawn, 0),
+        [113, 0, 0, 255] => (TileType::Goal, 0),
+        [0, 200, 0, 255] => (TileType::TerrainUp, 0),
+        [0, 155, 0, 255] => (TileType::TerrainCenter, 0),
+        [0, 109, 0, 255] => (TileType::TerrainDown, 0),
+        [0, 0, 200, 255] => (TileType::BuildUp, 0),
+        [0, 0, 109, 255] => (TileType::BuildDown, 0),
+        [200, 200, 0, 255] => (TileType::BlockerUp, 0),
+        [109, 109, 0, 255] => (TileType::BlockerDown, 0),
+        [200, 0, 200, 255] => (TileType::TurretUp, 0),
+        [109, 0, 109, 255] => (TileType::TurretDown, 0),
+        //Background, which the level gives a variant rolled from its seed. The shipped levels
+        //carry a pure blue pixel by their top right corner, which has always read as background.
+        [255, 255, 255, 255] | [0, 0, 255, 255] | [_, _, _, 0] => (TileType::Empty, 0),
+        _ => {
+          return Err(LoadError::UnknownColour {
+            level: name.to_owned(),
+            x: grid_pos.0,
+            y: grid_pos.1,
+            rgba: *p,
+          })
+        }
+      };
+
+      Ok(BaseTile {
+        kind,
+        variant,
+        grid_pos,
+        index: i,
+        size: (1, 1),
       })
-      .collect::<Vec<_>>();
-    let lvl_config = read_toml(file_name);
-
-    // LevelConfig {
-    //   enemies: Vec::new(),
-    //   health: 100,
-    //   towers: TowerSettings {
-    //     block_down: Some(1),
-    //     block_up: Some(1),
-    //     lava: Some(1),
-    //   },
-    // }
-
-    Level::new(lvl_data.width as usize, tiles, lvl_config)
-  }))
-  .await;
-
-  Levels::new(lvls)
+    })
+    .collect::<Result<Vec<_>, _>>()?;
+
+  if !tiles.iter().any(|t| t.kind == TileType::Spawn) {
+    return Err(LoadError::NoSpawn {
+      level: name.to_owned(),
+    });
+  }
+  Ok(Level::new(
+    name,
+    width,
+    tiles,
+    config,
+    seed,
+    tower_table,
+    enemy_table,
+  ))
 }
 
-fn read_toml(file_name: &str) -> LevelConfig {
-  let toml_str =
-    read_to_string(level_path(&format!("{}.toml", file_name))).unwrap_or(String::new());
-  let cfg: Result<LevelConfig, Error> = toml::from_str(&toml_str);
-  if let Ok(lvl_config) = cfg {
-    println!("cfg: {:?}", lvl_config);
-    return lvl_config;
+fn read_toml<T: serde::de::DeserializeOwned>(path: &str) -> Result<T, LoadError> {
+  let toml_str = read_to_string(path).map_err(|e| file_error(path, e))?;
+  toml::from_str(&toml_str).map_err(|error| LoadError::Toml {
+    path: path.to_owned(),
+    error,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::enemy::Pose;
+  use enum_map::Enum;
+
+  const WHITE: [u8; 4] = [255, 255, 255, 255];
+  const SPAWN: [u8; 4] = [213, 0, 0, 255];
+
+  fn config() -> LevelConfig {
+    toml::from_str("enemies = []\n[towers]\n").unwrap()
+  }
+
+  fn parse(pixels: &[[u8; 4]], config: LevelConfig) -> Result<Level, LoadError> {
+    parse_level(
+      "test",
+      2,
+      pixels,
+      config,
+      0,
+      read_tower_table().unwrap(),
+      read_enemy_table().unwrap(),
+    )
+  }
+
+  #[test]
+  fn reports_unknown_colour_position() {
+    let pixels = [SPAWN, WHITE, WHITE, [12, 34, 56, 255]];
+    match parse(&pixels, config()) {
+      Err(LoadError::UnknownColour { x, y, rgba, .. }) => {
+        assert_eq!((x, y, rgba), (1, 1, [12, 34, 56, 255]))
+      }
+      _ => panic!("expected an unknown colour error"),
+    }
+  }
+
+  #[test]
+  fn campaigns_need_levels() {
+    let empty: Campaign = toml::from_str("levels = []").unwrap();
+    assert!(matches!(
+      check_campaign(empty),
+      Err(LoadError::EmptyCampaign)
+    ));
+    let dangling: Campaign =
+      toml::from_str("[[levels]]\nfile = \"level1\"\nnext = [\"level9\"]").unwrap();
+    assert!(matches!(
+      check_campaign(dangling),
+      Err(LoadError::UnknownLevel { .. })
+    ));
+  }
+
+  #[test]
+  fn requires_a_spawn() {
+    let result = parse(&[WHITE; 4], config());
+    assert!(matches!(result, Err(LoadError::NoSpawn { .. })));
+    assert!(parse(&[SPAWN, WHITE, WHITE, WHITE], config()).is_ok());
+  }
+
+  #[test]
+  fn reads_placed_towers() {
+    let pixels = [
+      SPAWN,
+      [200, 200, 0, 255],
+      [109, 0, 109, 255],
+      [200, 0, 200, 255],
+    ];
+    let lvl = parse(&pixels, config()).unwrap();
+    let kinds: Vec<TileType> = lvl.tiles.iter().map(|t| t.kind).collect();
+    assert_eq!(kinds[1], TileType::BlockerUp);
+    assert_eq!(kinds[2], TileType::TurretDown);
+    assert_eq!(kinds[3], TileType::TurretUp);
+    assert_eq!(kinds[3].placed_tower(), Some(TowerType::Geyser));
+  }
+
+  #[test]
+  fn picks_animation_frames() {
+    assert_eq!(frame_index(4, Some(0.1), true, 0.05), 0);
+    assert_eq!(frame_index(4, Some(0.1), true, 0.25), 2);
+    assert_eq!(frame_index(4, Some(0.1), true, 0.45), 0);
+    assert_eq!(frame_index(4, Some(0.1), false, 0.45), 3);
+    assert_eq!(frame_index(3, None, true, 10.0), 0);
+  }
+
+  #[test]
+  fn sprite_manifest_files_exist() {
+    let defs: HashMap<String, SpriteDef> =
+      read_toml(&format!("{}/{}", TEXTURE_PATH, SPRITE_FILE)).unwrap();
+    assert!(defs.contains_key(MISSING_SPRITE));
+    for (id, def) in &defs {
+      assert!(!def.files().is_empty(), "{} has no frames", id);
+      for file in def.files() {
+        assert!(
+          std::path::Path::new(&tex_path(file)).exists(),
+          "{} uses missing file {}",
+          id,
+          file
+        );
+      }
+    }
+  }
+
+  #[test]
+  fn tower_table_sprites_exist() {
+    let defs: HashMap<String, SpriteDef> =
+      read_toml(&format!("{}/{}", TEXTURE_PATH, SPRITE_FILE)).unwrap();
+    let table = read_tower_table().unwrap();
+    for kind in (0..TowerType::LENGTH).map(TowerType::from_usize) {
+      //Every tier looks different, by its art or by its tint.
+      let mut looks = Vec::new();
+      for tier in 0..table.tier_count(kind) {
+        let stats = table.get(kind, tier);
+        let sprite = stats.sprite.as_ref().unwrap();
+        assert!(defs.contains_key(sprite), "{} is not a sprite", sprite);
+        let look = (defs[sprite].files(), stats.tint);
+        assert!(
+          !looks.contains(&look),
+          "{:?} tier {} looks like one before",
+          kind,
+          tier
+        );
+        looks.push(look);
+      }
+    }
+  }
+
+  #[test]
+  fn tiers_keep_what_they_do_not_change() {
+    let table = read_tower_table().unwrap();
+    let base = table.get(TowerType::Lava, 0);
+    let upgraded = table.get(TowerType::Lava, 1);
+    assert_eq!(upgraded.hitbox, base.hitbox);
+    assert!(upgraded.interval < base.interval);
+    //Past the last tier stays at the last tier.
+    let last = table.tier_count(TowerType::Lava) - 1;
+    assert_eq!(
+      table.get(TowerType::Lava, last + 5),
+      table.get(TowerType::Lava, last)
+    );
+  }
+
+  #[test]
+  fn enemy_table_sprites_exist() {
+    let sprites: HashMap<String, SpriteDef> =
+      read_toml(&format!("{}/{}", TEXTURE_PATH, SPRITE_FILE)).unwrap();
+    let table = read_enemy_table().unwrap();
+    assert!(table.get(crate::enemy::DEFAULT_ENEMY).is_some());
+    for (kind, def) in table.iter() {
+      for pose in (0..Pose::LENGTH).map(Pose::from_usize) {
+        let sprite = pose.sprite(&def.sprite);
+        assert!(sprites.contains_key(&sprite), "{} has no {}", kind, sprite);
+      }
+    }
+  }
+
+  #[test]
+  fn enemy_table_needs_the_default_enemy() {
+    let mut defs: HashMap<String, EnemyDef> = read_toml(ENEMY_FILE).unwrap();
+    defs.remove(crate::enemy::DEFAULT_ENEMY);
+    assert_eq!(
+      EnemyTable::new(defs).unwrap_err(),
+      (
+        crate::enemy::DEFAULT_ENEMY.to_owned(),
+        "no definition".to_owned()
+      )
+    );
+  }
+
+  #[test]
+  fn spans_name_known_enemies() {
+    let pixels = [SPAWN, WHITE, WHITE, WHITE];
+    let spans = |kind: &str| {
+      toml::from_str::<LevelConfig>(&format!(
+        "[[enemies]]\ntime = 1\ncount = 1\nkind = \"{}\"\n[towers]\n",
+        kind
+      ))
+      .unwrap()
+    };
+    assert!(parse(&pixels, spans("brute")).is_ok());
+    match parse(&pixels, spans("dragon")) {
+      Err(LoadError::UnknownEnemy { kind, .. }) => assert_eq!(kind, "dragon"),
+      _ => panic!("expected an unknown enemy error"),
+    }
+  }
+
+  #[test]
+  fn config_errors_name_the_key() {
+    let path = std::env::temp_dir().join(format!("tddown-config-{}.toml", std::process::id()));
+    std::fs::write(&path, "enemies = []\nhealth = \"lots\"\n[towers]\n").unwrap();
+    let result = read_toml::<LevelConfig>(path.to_str().unwrap());
+    std::fs::remove_file(&path).unwrap();
+    match result {
+      Err(e @ LoadError::Toml { .. }) => assert!(e.to_string().contains("health"), "{}", e),
+      _ => panic!("expected a TOML error"),
+    }
   }
-  panic!("Could not read level config file! {:?}", cfg);
 }
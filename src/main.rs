@@ -1,27 +1,42 @@
 #![allow(dead_code)]
-use deb::{draw_debug_texts, DebugSettings};
-use effects::{spawn_effect, Effect, EffectKind, Effects};
-use enemy::Enemy;
-use loading::{load_levels, load_textures};
+use bindings::{Action, Bindings};
+use deb::DebugSettings;
+use game::Game;
+use loading::{load_levels, load_textures, LoadError, Textures};
 use macroquad::prelude::*;
-use tower::Towers;
-use wrld::World;
+use replay::Replay;
+use save::SaveProfile;
+use std::{env, path::Path};
 
 #[macro_use]
 extern crate enum_map;
 
+mod atlas;
+mod bindings;
 mod buildable;
+mod camera;
 mod deb;
 mod effects;
 mod emath;
 mod enemy;
+mod game;
+mod gamepad;
+mod input;
 mod level;
 mod loading;
+mod physics;
 mod rect;
+mod render;
+mod replay;
+mod save;
+mod screens;
+mod sim;
+mod spatial;
 mod spawner;
 mod tile;
 mod tower;
 mod ui;
+mod view;
 mod wrld;
 
 fn window_conf() -> Conf {
@@ -33,48 +48,72 @@ fn window_conf() -> Conf {
   }
 }
 
-#[macroquad::main(window_conf)]
-async fn main() {
-  let texs = load_textures().await;
-  let lvls = load_levels(&texs).await;
+async fn load() -> Result<(Textures, Game), LoadError> {
+  let texs = load_textures()?;
+  let lvls = load_levels()?;
 
   let deb_state = DebugSettings {
     zero_offset_initial_camera: true,
     draw_rects: true,
     ..Default::default()
   };
-  let mut effects: Vec<Effects> = Vec::new();
-  let mut wrld = World::new(lvls.get_level(0), texs);
-  let mut towers = Towers::new(&wrld);
-  let mut enemies: Vec<Enemy> = Vec::new();
-
-  loop {
-    clear_background(BLACK);
+  let mut notices = Vec::new();
+  let profile = SaveProfile::load().unwrap_or_else(|e| {
+    notices.push(format!("Ignoring save file {}", e));
+    SaveProfile::default()
+  });
+  let bindings = Bindings::load().unwrap_or_else(|e| {
+    notices.push(format!("Ignoring bindings file {}", e));
+    Bindings::defaults()
+  });
+  if let Err(e) = gamepad::init() {
+    notices.push(format!("No gamepad support: {}", e));
+  }
+  let mut game = Game::new(lvls, profile, bindings, deb_state)?;
+  for notice in notices {
+    game.notify(notice);
+  }
 
-    wrld.update(&mut enemies, &towers, &effects);
-    towers.update(&mut wrld);
-    let spawn_effects = towers.get_spawns(&wrld);
-    for (kind, pos) in spawn_effects {
-      spawn_effect(&mut effects, &wrld.textures, kind, pos);
-    }
+  //`tddown --replay <file>` plays a recorded game back instead of taking input.
+  let args: Vec<String> = env::args().collect();
+  if let Some(i) = args.iter().position(|a| a == "--replay") {
+    let path = args.get(i + 1).ok_or_else(|| LoadError::Replay {
+      message: "--replay needs a file path".to_owned(),
+    })?;
+    let replay = Replay::load(Path::new(path)).map_err(|message| LoadError::Replay { message })?;
+    game.play_replay(replay)?;
+  }
+  Ok((texs, game))
+}
 
-    let mut effects_to_spawn: Vec<(EffectKind, (usize, usize))> = Vec::new();
-    effects.retain_mut(|effect| {
-      let ret = effect.update(&wrld);
-      if let Some(spawn) = ret.spawn {
-        effects_to_spawn.push(spawn);
+#[macroquad::main(window_conf)]
+async fn main() {
+  let (texs, mut game) = match load().await {
+    Ok(loaded) => loaded,
+    Err(e) => {
+      eprintln!("{}", e);
+      let text = e.to_string();
+      let bindings = Bindings::load().unwrap_or_else(|_| Bindings::defaults());
+      loop {
+        gamepad::update();
+        if bindings.is_released(Action::Back) {
+          break;
+        }
+        clear_background(BLACK);
+        screens::error(
+          "Could not load the game",
+          &text,
+          &bindings.describe(Action::Back),
+        );
+        next_frame().await
       }
-      // println!("|- keep effect {:?}, spawn: {:?}", ret.keep, ret.spawn);
-      ret.keep
-    });
-
-    for (kind, pos) in effects_to_spawn {
-      spawn_effect(&mut effects, &wrld.textures, kind, pos);
+      return;
     }
+  };
 
-    ui::draw(&mut wrld, &towers);
-
-    draw_debug_texts(&deb_state, &wrld);
+  loop {
+    clear_background(BLACK);
+    game.frame(&texs);
     next_frame().await
   }
 }
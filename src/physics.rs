@@ -0,0 +1,301 @@
+use macroquad::prelude::{vec2, Vec2};
+
+use crate::{
+  buildable::Buildable,
+  rect::{Collidable, Rect},
+  tile::TileType,
+  tower::{TowerType, Towers},
+  wrld::World,
+};
+
+/** Overlaps thinner than this many pixels count as touching, so float error can not stick. */
+const EPSILON: f32 = 0.001;
+
+/**
+ * A box in level pixels with float coordinates, which may lie outside the level. Unlike `Rect`
+ * it spans `min` up to but not including `max`, so boxes that only touch do not overlap.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+  pub min: Vec2,
+  pub max: Vec2,
+}
+
+impl Aabb {
+  pub fn new(min: Vec2, max: Vec2) -> Aabb {
+    Aabb { min, max }
+  }
+  /** The box `offsets` (`[left, top, right, bottom]`) covers from `pos`. */
+  pub fn at(pos: Vec2, offsets: &Rect) -> Aabb {
+    Aabb::new(
+      pos + vec2(offsets.left as f32, offsets.top as f32),
+      pos + vec2(offsets.right as f32, offsets.bottom as f32),
+    )
+  }
+  pub fn offset(&self, by: Vec2) -> Aabb {
+    Aabb::new(self.min + by, self.max + by)
+  }
+  /** Rounded to whole pixels, cut off at the top and left level edges. */
+  pub fn to_rect(self) -> Rect {
+    let min = self.min.round().max(Vec2::ZERO);
+    let max = self.max.round().max(min);
+    Rect::new(
+      min.x as usize,
+      min.y as usize,
+      max.x as usize,
+      max.y as usize,
+    )
+  }
+  pub fn overlaps(&self, other: &Aabb) -> bool {
+    self.min.x + EPSILON < other.max.x
+      && self.max.x - EPSILON > other.min.x
+      && self.min.y + EPSILON < other.max.y
+      && self.max.y - EPSILON > other.min.y
+  }
+  /** The smallest box holding both. */
+  fn union(&self, other: &Aabb) -> Aabb {
+    Aabb::new(self.min.min(other.min), self.max.max(other.max))
+  }
+}
+
+impl From<&Rect> for Aabb {
+  fn from(rect: &Rect) -> Aabb {
+    Aabb::new(
+      vec2(rect.left as f32, rect.top as f32),
+      vec2(rect.right as f32, rect.bottom as f32),
+    )
+  }
+}
+
+/** What a mover ran into. */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Hit {
+  Tile {
+    grid_pos: (usize, usize),
+    kind: TileType,
+  },
+  Tower {
+    grid_pos: (usize, usize),
+    kind: TowerType,
+  },
+  /** The outside of the level, which is solid all around. */
+  Edge,
+}
+
+/** Where a sweep stopped. */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Contact {
+  pub hit: Hit,
+  /** Unit vector pointing away from the side that was hit. */
+  pub normal: Vec2,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sweep {
+  /** The part of the move that was made before running into something. */
+  pub moved: Vec2,
+  pub contact: Option<Contact>,
+}
+
+/**
+ * Fraction of `delta` that `mover` travels before it runs into `solid`, with the normal of the
+ * side it hits. None if it misses, or already overlaps `solid` and can move out of it.
+ */
+pub fn time_of_impact(mover: &Aabb, solid: &Aabb, delta: Vec2) -> Option<(f32, Vec2)> {
+  let (entry_x, exit_x) = axis_times(mover.min.x, mover.max.x, solid.min.x, solid.max.x, delta.x)?;
+  let (entry_y, exit_y) = axis_times(mover.min.y, mover.max.y, solid.min.y, solid.max.y, delta.y)?;
+  let entry = entry_x.max(entry_y);
+  let exit = exit_x.min(exit_y);
+  if !(0.0..=1.0).contains(&entry) || entry >= exit {
+    return None;
+  }
+  let normal = if entry_x > entry_y {
+    vec2(-delta.x.signum(), 0.0)
+  } else {
+    vec2(0.0, -delta.y.signum())
+  };
+  Some((entry, normal))
+}
+
+/**
+ * When the spans `[min, max)` and `[other_min, other_max)` start and stop overlapping while the
+ * first moves by `d`, as fractions of `d`.
+ */
+fn axis_times(min: f32, max: f32, other_min: f32, other_max: f32, d: f32) -> Option<(f32, f32)> {
+  let (gap, depth) = if d > 0.0 {
+    (other_min - max, other_max - min)
+  } else {
+    (min - other_max, max - other_min)
+  };
+  if d == 0.0 {
+    //Not moving on this axis, so they overlap for the whole move or not at all.
+    return (gap < -EPSILON && depth > EPSILON).then_some((f32::NEG_INFINITY, f32::INFINITY));
+  }
+  let gap = if gap > -EPSILON { gap.max(0.0) } else { gap };
+  Some((gap / d.abs(), depth / d.abs()))
+}
+
+/** What movers collide with: the tiles that are not passable, the level edges and the towers. */
+pub struct Colliders<'a> {
+  wrld: &'a World,
+  towers: Option<&'a Towers>,
+  /** Grid position of a tower the mover passes through. */
+  skip_tower: Option<(usize, usize)>,
+}
+
+impl<'a> Colliders<'a> {
+  /** The level's tiles and edges, and the solid towers in `towers` if given. */
+  pub fn new(wrld: &'a World, towers: Option<&'a Towers>) -> Colliders<'a> {
+    Colliders {
+      wrld,
+      towers,
+      skip_tower: None,
+    }
+  }
+  /** Lets movers through the tower at `grid_pos`. */
+  pub fn skipping(mut self, grid_pos: Option<(usize, usize)>) -> Colliders<'a> {
+    self.skip_tower = grid_pos;
+    self
+  }
+
+  /** Calls `f` with every solid in the tiles `area` touches. */
+  fn each_solid(&self, area: &Aabb, mut f: impl FnMut(Hit, Aabb)) {
+    let lvl = self.wrld.get_lvl();
+    let cell = |v: f32| (v / 32.0).floor() as isize;
+    for y in cell(area.min.y)..=cell(area.max.y) {
+      for x in cell(area.min.x)..=cell(area.max.x) {
+        if x < 0 || y < 0 || x as usize >= lvl.width || y as usize >= lvl.height {
+          let min = vec2(x as f32, y as f32) * 32.0;
+          f(Hit::Edge, Aabb::new(min, min + Vec2::splat(32.0)));
+        } else if let Some(tile) = self.wrld.get_tile(&(x as usize), &(y as usize)) {
+          if !tile.is_passable() {
+            let hit = Hit::Tile {
+              grid_pos: tile.grid_pos(),
+              kind: *tile.kind(),
+            };
+            f(hit, Aabb::from(tile.get_hitbox()));
+          }
+        }
+      }
+    }
+
+    if let Some(towers) = self.towers {
+      //The tower index wants a rect inside the level, touching counts.
+      let min = area.min.floor().max(Vec2::ZERO);
+      let max = area.max.ceil().max(min);
+      let rect = Rect::new(
+        min.x as usize,
+        min.y as usize,
+        max.x as usize,
+        max.y as usize,
+      );
+      for tower in towers.get_collided_towers(&rect) {
+        if Some(tower.get_grid_pos()) == self.skip_tower {
+          continue;
+        }
+        let hit = Hit::Tower {
+          grid_pos: tower.get_grid_pos(),
+          kind: tower.get_kind(),
+        };
+        f(hit, Aabb::from(tower.get_hitbox()));
+      }
+    }
+  }
+
+  /** Whether `aabb` overlaps anything solid. */
+  pub fn blocked(&self, aabb: &Aabb) -> bool {
+    let mut blocked = false;
+    self.each_solid(aabb, |_, solid| blocked |= solid.overlaps(aabb));
+    blocked
+  }
+
+  /**
+   * Moves `aabb` by `delta`, stopping at the first solid in the way. Solids it already overlaps
+   * do not stop it, so it can move out of them.
+   */
+  pub fn sweep(&self, aabb: &Aabb, delta: Vec2) -> Sweep {
+    let mut first: Option<(f32, Contact, Aabb)> = None;
+    self.each_solid(&aabb.union(&aabb.offset(delta)), |hit, solid| {
+      if let Some((time, normal)) = time_of_impact(aabb, &solid, delta) {
+        let earlier = match &first {
+          Some((t, _, _)) => time < *t,
+          None => true,
+        };
+        if earlier {
+          first = Some((time, Contact { hit, normal }, solid));
+        }
+      }
+    });
+
+    match first {
+      None => Sweep {
+        moved: delta,
+        contact: None,
+      },
+      Some((time, contact, solid)) => {
+        let mut moved = delta * time;
+        //Lands exactly on the side it hit, so the next sweep starts out touching it.
+        if contact.normal.x > 0.0 {
+          moved.x = solid.max.x - aabb.min.x;
+        } else if contact.normal.x < 0.0 {
+          moved.x = solid.min.x - aabb.max.x;
+        } else if contact.normal.y > 0.0 {
+          moved.y = solid.max.y - aabb.min.y;
+        } else if contact.normal.y < 0.0 {
+          moved.y = solid.min.y - aabb.max.y;
+        }
+        Sweep {
+          moved,
+          contact: Some(contact),
+        }
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn square(x: f32, y: f32, size: f32) -> Aabb {
+    Aabb::new(vec2(x, y), vec2(x + size, y + size))
+  }
+
+  #[test]
+  fn finds_the_time_of_impact() {
+    let wall = square(100.0, 0.0, 32.0);
+    let mover = square(0.0, 0.0, 20.0);
+    assert_eq!(
+      time_of_impact(&mover, &wall, vec2(160.0, 0.0)),
+      Some((0.5, vec2(-1.0, 0.0)))
+    );
+    //Too short, or moving away.
+    assert_eq!(time_of_impact(&mover, &wall, vec2(60.0, 0.0)), None);
+    assert_eq!(time_of_impact(&mover, &wall, vec2(-60.0, 0.0)), None);
+    //A move far longer than the wall is thick still hits it.
+    assert!(time_of_impact(&mover, &wall, vec2(10_000.0, 0.0)).is_some());
+  }
+
+  #[test]
+  fn touching_stops_but_sliding_does_not() {
+    let floor = square(0.0, 32.0, 32.0);
+    let standing = square(4.0, 12.0, 20.0);
+    assert_eq!(
+      time_of_impact(&standing, &floor, vec2(0.0, 5.0)),
+      Some((0.0, vec2(0.0, -1.0)))
+    );
+    assert_eq!(time_of_impact(&standing, &floor, vec2(5.0, 0.0)), None);
+    //Float error on the shared edge still counts as touching.
+    let sunk = standing.offset(vec2(0.0, EPSILON / 2.0));
+    assert!(time_of_impact(&sunk, &floor, vec2(0.0, 5.0)).is_some());
+    assert!(!sunk.overlaps(&floor));
+  }
+
+  #[test]
+  fn moves_out_of_overlapping_solids() {
+    let solid = square(0.0, 0.0, 32.0);
+    let inside = square(10.0, 10.0, 8.0);
+    assert!(inside.overlaps(&solid));
+    assert_eq!(time_of_impact(&inside, &solid, vec2(40.0, 0.0)), None);
+  }
+}
@@ -96,7 +96,7 @@ impl Sub<&(usize, usize)> for Rect {
 
 impl Collidable for Rect {
   fn get_hitbox(&self) -> &Rect {
-    &self
+    self
   }
 }
 
@@ -116,7 +116,7 @@ mod tests {
     let a = Rect::new(0, 0, 10, 10);
     let b = a;
     let result = a.intersecting(&b);
-    assert_eq!(result, true);
+    assert!(result);
   }
 
   #[test]
@@ -124,7 +124,7 @@ mod tests {
     let a = Rect::new(0, 0, 10, 10);
     let b = Rect::new(5, 5, 15, 15);
     let result = a.intersecting(&b);
-    assert_eq!(result, true);
+    assert!(result);
   }
 
   #[test]
@@ -132,14 +132,14 @@ mod tests {
     let a = Rect::new(0, 0, 10, 10);
     let b = Rect::new(10, 0, 20, 20);
     let result = a.intersecting(&b);
-    assert_eq!(result, true);
+    assert!(result);
   }
   #[test]
   fn collide_touching_top() {
     let a = Rect::new(0, 0, 10, 10);
     let b = Rect::new(0, 10, 20, 20);
     let result = a.intersecting(&b);
-    assert_eq!(result, true);
+    assert!(result);
   }
 
   #[test]
@@ -147,7 +147,7 @@ mod tests {
     let a = Rect::new(0, 0, 10, 10);
     let b = Rect::new(15, 15, 25, 25);
     let result = a.intersecting(&b);
-    assert_eq!(result, false);
+    assert!(!result);
   }
 
   #[test]
@@ -155,6 +155,6 @@ mod tests {
     let a = Rect::new(480, 224, 512, 288);
     let b = Rect::new(33, 32, 33, 34);
     let result = a.intersecting(&b);
-    assert_eq!(result, false);
+    assert!(!result);
   }
 }
@@ -0,0 +1,191 @@
+use macroquad::{
+  camera::{set_camera, set_default_camera, Camera2D},
+  prelude::{vec2, Color, FilterMode, WHITE, YELLOW},
+  shapes::draw_rectangle_lines,
+  texture::{draw_texture_ex, render_target, DrawTextureParams, RenderTarget},
+  window::clear_background,
+};
+
+use crate::{
+  bindings::Bindings,
+  buildable::Buildable,
+  camera::Camera,
+  deb::DEBUG,
+  effects::Effect,
+  loading::Textures,
+  rect::{Collidable, Rect},
+  sim::Sim,
+  ui,
+  view::View,
+  wrld::World,
+};
+
+/** Tiles per side of one cached chunk of the tile layer. */
+const CHUNK_TILES: usize = 16;
+
+struct Chunk {
+  /** Covered tiles, in grid cells. */
+  grid_rect: Rect,
+  target: RenderTarget,
+}
+
+/**
+ * The static tile layer, pre-rendered in chunks at the current zoom so a frame draws a few
+ * textures instead of every tile. Rebuilt when the zoom changes or after `clear`.
+ */
+pub struct TileCache {
+  zoom: f32,
+  chunks: Vec<Chunk>,
+}
+
+impl TileCache {
+  pub fn new() -> TileCache {
+    TileCache {
+      zoom: 0.0,
+      chunks: Vec::new(),
+    }
+  }
+
+  /** Drops the chunks, call when the level changes. */
+  pub fn clear(&mut self) {
+    for chunk in self.chunks.drain(..) {
+      chunk.target.delete();
+    }
+  }
+
+  fn rebuild(&mut self, wrld: &World, texs: &Textures, camera: &Camera) {
+    self.clear();
+    self.zoom = camera.zoom;
+    let lvl = wrld.get_lvl();
+    for top in (0..lvl.height).step_by(CHUNK_TILES) {
+      for left in (0..lvl.width).step_by(CHUNK_TILES) {
+        let grid_rect = Rect::new(
+          left,
+          top,
+          (left + CHUNK_TILES).min(lvl.width),
+          (top + CHUNK_TILES).min(lvl.height),
+        );
+        let target = render_chunk(wrld, texs, &grid_rect, 32.0 * camera.zoom);
+        self.chunks.push(Chunk { grid_rect, target });
+      }
+    }
+    set_camera(&camera.camera2d());
+  }
+
+  /** Draws the visible chunks under the camera, rebuilding them first if the zoom changed. */
+  pub fn draw(&mut self, wrld: &World, texs: &Textures, camera: &Camera) {
+    if self.chunks.is_empty() || self.zoom != camera.zoom {
+      self.rebuild(wrld, texs, camera);
+    }
+    for chunk in &self.chunks {
+      let g = &chunk.grid_rect;
+      let rect = Rect::new(g.left * 32, g.top * 32, g.right * 32, g.bottom * 32);
+      if !camera.is_visible(&rect) {
+        continue;
+      }
+      draw_texture_ex(
+        chunk.target.texture,
+        rect.left as f32,
+        rect.top as f32,
+        WHITE,
+        DrawTextureParams {
+          dest_size: Some(vec2(rect.width() as f32, rect.height() as f32)),
+          ..Default::default()
+        },
+      );
+    }
+  }
+}
+
+impl Default for TileCache {
+  fn default() -> Self {
+    TileCache::new()
+  }
+}
+
+/** Draws the tiles in `grid_rect` into a new render target, `grid_size` pixels per tile. */
+fn render_chunk(wrld: &World, texs: &Textures, grid_rect: &Rect, grid_size: f32) -> RenderTarget {
+  let width = grid_rect.width() as f32 * grid_size;
+  let height = grid_rect.height() as f32 * grid_size;
+  let target = render_target(width as u32, height as u32);
+  target.texture.set_filter(FilterMode::Nearest);
+  //Y up, so the texture comes out the right way round when drawn.
+  set_camera(&Camera2D {
+    zoom: vec2(2.0 / width, 2.0 / height),
+    target: vec2(width / 2.0, height / 2.0),
+    render_target: Some(target),
+    ..Default::default()
+  });
+  clear_background(Color::new(0.0, 0.0, 0.0, 0.0));
+  for y in grid_rect.top..grid_rect.bottom {
+    for x in grid_rect.left..grid_rect.right {
+      if let Some(t) = wrld.get_tile(&x, &y) {
+        let tx = (x - grid_rect.left) as f32 * grid_size;
+        let ty = (y - grid_rect.top) as f32 * grid_size;
+        t.draw_at(texs, tx, ty, grid_size, WHITE);
+      }
+    }
+  }
+  target
+}
+
+/**
+ * Draws the current simulation state, the level under the camera and the UI on top in screen
+ * pixels. Reads the simulation, never advances it.
+ */
+pub fn draw(tiles: &mut TileCache, sim: &Sim, texs: &Textures, view: &View, bindings: &Bindings) {
+  let camera = &view.camera;
+  set_camera(&camera.camera2d());
+
+  //DRAW TILES
+  tiles.draw(&sim.wrld, texs, camera);
+  let build_dir = sim
+    .wrld
+    .selected_tower_type
+    .map(|kind| sim.wrld.get_lvl().tower_table.def(kind).direction);
+  for t in &sim.wrld.tiles {
+    if !camera.is_visible(t.get_hitbox()) {
+      continue;
+    }
+    if t.is_highlighted(build_dir) {
+      t.draw(texs, build_dir);
+    }
+    if DEBUG.draw_rects {
+      t.debug_draw();
+    }
+  }
+
+  for corpse in &view.corpses {
+    if camera.is_visible(corpse.get_draw_rect()) {
+      corpse.draw(texs);
+    }
+  }
+  for e in &sim.enemies {
+    if camera.is_visible(&e.get_draw_rect()) {
+      e.draw(texs);
+    }
+  }
+  sim.towers.draw(texs, camera);
+  if let Some(tower) = view
+    .selected_tower
+    .and_then(|g| sim.towers.get_tower(&sim.wrld, g))
+  {
+    let r = tower.get_draw_rect();
+    draw_rectangle_lines(
+      r.left as f32,
+      r.top as f32,
+      r.width() as f32,
+      r.height() as f32,
+      2.0,
+      YELLOW,
+    );
+  }
+  for effect in &sim.effects {
+    if camera.is_visible(&effect.get_draw_pos()) {
+      effect.draw(texs);
+    }
+  }
+
+  set_default_camera();
+  ui::draw(&sim.wrld, &sim.towers, texs, view, bindings);
+}
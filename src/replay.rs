@@ -0,0 +1,231 @@
+use enum_map::Enum;
+use std::{
+  fmt::{self, Display},
+  fs::{create_dir_all, read_to_string, write},
+  path::Path,
+};
+
+use crate::{input::Command, tower::TowerType};
+
+const HEADER: &str = "tddown replay 1";
+pub static REPLAY_PATH: &str = "replays";
+
+/**
+ * Every command applied to a level, stamped with the tick it was applied on. Together with the
+ * level seed this rebuilds the exact game.
+ *
+ * Stored as text, one command per line:
+ * ```text
+ * tddown replay 1
+ * level level1
+ * seed 1234
+ * 10 select Lava
+ * 10 place 6 5
+ * ```
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Replay {
+  pub level: String,
+  pub seed: u64,
+  pub entries: Vec<(u64, Command)>,
+}
+
+impl Replay {
+  pub fn new(level: &str, seed: u64) -> Replay {
+    Replay {
+      level: level.to_owned(),
+      seed,
+      entries: Vec::new(),
+    }
+  }
+  pub fn push(&mut self, tick: u64, command: Command) {
+    self.entries.push((tick, command));
+  }
+
+  pub fn parse(text: &str) -> Result<Replay, String> {
+    let mut lines = text
+      .lines()
+      .enumerate()
+      .map(|(i, l)| (i + 1, l.trim()))
+      .filter(|(_, l)| !l.is_empty());
+
+    match lines.next() {
+      Some((_, HEADER)) => {}
+      _ => return Err(format!("missing header \"{}\"", HEADER)),
+    }
+    let level = match lines.next() {
+      Some((_, l)) if l.starts_with("level ") => l["level ".len()..].to_owned(),
+      _ => return Err("missing level line".to_owned()),
+    };
+    let seed = match lines.next() {
+      Some((n, l)) if l.starts_with("seed ") => l["seed ".len()..]
+        .parse()
+        .map_err(|_| format!("line {}: invalid seed", n))?,
+      _ => return Err("missing seed line".to_owned()),
+    };
+
+    let mut replay = Replay::new(&level, seed);
+    for (n, line) in lines {
+      let parts: Vec<&str> = line.split_whitespace().collect();
+      let tick = parts[0]
+        .parse()
+        .map_err(|_| format!("line {}: invalid tick \"{}\"", n, parts[0]))?;
+      let command =
+        parse_command(&parts[1..]).ok_or_else(|| format!("line {}: invalid command", n))?;
+      replay.push(tick, command);
+    }
+    Ok(replay)
+  }
+
+  pub fn load(path: &Path) -> Result<Replay, String> {
+    let text = read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Replay::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+  }
+  pub fn save(&self, path: &Path) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+      create_dir_all(dir)?;
+    }
+    write(path, self.to_string())
+  }
+}
+
+impl Display for Replay {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    writeln!(f, "{}", HEADER)?;
+    writeln!(f, "level {}", self.level)?;
+    writeln!(f, "seed {}", self.seed)?;
+    for (tick, command) in &self.entries {
+      match command {
+        Command::SelectTower(kind) => writeln!(f, "{} select {:?}", tick, kind)?,
+        Command::Deselect => writeln!(f, "{} deselect", tick)?,
+        Command::PlaceTower((x, y)) => writeln!(f, "{} place {} {}", tick, x, y)?,
+        Command::RemoveTower((x, y)) => writeln!(f, "{} remove {} {}", tick, x, y)?,
+        Command::UpgradeTower((x, y)) => writeln!(f, "{} upgrade {} {}", tick, x, y)?,
+        Command::SpeedUp => writeln!(f, "{} speed_up", tick)?,
+        Command::SpeedDown => writeln!(f, "{} speed_down", tick)?,
+        Command::Spawn => writeln!(f, "{} spawn", tick)?,
+      }
+    }
+    Ok(())
+  }
+}
+
+fn parse_command(parts: &[&str]) -> Option<Command> {
+  match parts {
+    ["select", kind] => (0..TowerType::LENGTH)
+      .map(TowerType::from_usize)
+      .find(|t| format!("{:?}", t) == *kind)
+      .map(Command::SelectTower),
+    ["deselect"] => Some(Command::Deselect),
+    ["place", x, y] => Some(Command::PlaceTower((x.parse().ok()?, y.parse().ok()?))),
+    ["remove", x, y] => Some(Command::RemoveTower((x.parse().ok()?, y.parse().ok()?))),
+    ["upgrade", x, y] => Some(Command::UpgradeTower((x.parse().ok()?, y.parse().ok()?))),
+    ["speed_up"] => Some(Command::SpeedUp),
+    ["speed_down"] => Some(Command::SpeedDown),
+    ["spawn"] => Some(Command::Spawn),
+    _ => None,
+  }
+}
+
+/** Feeds the commands of a replay back, tick by tick. */
+pub struct ReplayPlayer {
+  replay: Replay,
+  cursor: usize,
+}
+
+impl ReplayPlayer {
+  pub fn new(replay: Replay) -> ReplayPlayer {
+    ReplayPlayer { replay, cursor: 0 }
+  }
+  pub fn get_replay(&self) -> &Replay {
+    &self.replay
+  }
+  /** Returns the commands recorded for `tick`. Ticks must be asked for in order. */
+  pub fn take(&mut self, tick: u64) -> Vec<Command> {
+    let mut commands = Vec::new();
+    while let Some((t, command)) = self.replay.entries.get(self.cursor) {
+      if *t > tick {
+        break;
+      }
+      commands.push(*command);
+      self.cursor += 1;
+    }
+    commands
+  }
+  pub fn is_finished(&self) -> bool {
+    self.cursor >= self.replay.entries.len()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    level::Level,
+    loading::{read_enemy_table, read_level, read_tower_table},
+    sim::Sim,
+  };
+
+  fn read(name: &str) -> Level {
+    read_level(
+      name,
+      &read_tower_table().unwrap(),
+      &read_enemy_table().unwrap(),
+    )
+    .unwrap()
+  }
+
+  fn play(sim: &mut Sim, ticks: u64, mut next: impl FnMut(u64) -> Vec<Command>) {
+    for _ in 0..ticks {
+      let commands = next(sim.wrld.tick);
+      sim.tick(&commands);
+    }
+  }
+
+  #[test]
+  fn text_round_trip() {
+    let mut replay = Replay::new("level1", u64::MAX);
+    replay.push(3, Command::SelectTower(TowerType::BlockerDown));
+    replay.push(3, Command::PlaceTower((2, 6)));
+    replay.push(10, Command::UpgradeTower((2, 6)));
+    replay.push(20, Command::RemoveTower((2, 6)));
+    replay.push(40, Command::Deselect);
+    replay.push(41, Command::SpeedUp);
+    replay.push(41, Command::SpeedDown);
+    replay.push(90, Command::Spawn);
+    assert_eq!(Replay::parse(&replay.to_string()), Ok(replay));
+  }
+
+  #[test]
+  fn rejects_bad_lines() {
+    let text = format!("{}\nlevel level1\nseed 1\n10 place 6\n", HEADER);
+    assert_eq!(
+      Replay::parse(&text),
+      Err("line 4: invalid command".to_owned())
+    );
+  }
+
+  #[test]
+  fn playback_rebuilds_game() {
+    let mut recorded = Sim::new(read("level1"));
+    play(&mut recorded, 60 * 40, |tick| match tick {
+      5 => vec![
+        Command::SelectTower(TowerType::BlockerDown),
+        Command::PlaceTower((2, 6)),
+      ],
+      90 => vec![Command::Spawn, Command::SpeedUp],
+      _ => vec![],
+    });
+
+    let replay = Replay::parse(&recorded.replay.to_string()).unwrap();
+    let mut player = ReplayPlayer::new(replay);
+    let mut played = Sim::new(read(&player.get_replay().level));
+    play(&mut played, 60 * 40, |tick| player.take(tick));
+
+    assert!(player.is_finished());
+    assert_eq!(played.replay, recorded.replay);
+    assert_eq!(played.wrld.health, recorded.wrld.health);
+    let positions = |sim: &Sim| sim.enemies.iter().map(|e| e.pos).collect::<Vec<_>>();
+    assert_eq!(positions(&played), positions(&recorded));
+  }
+}
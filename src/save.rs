@@ -0,0 +1,176 @@
+use serde::{Deserialize, Serialize};
+use std::{
+  collections::{BTreeMap, HashSet},
+  env,
+  fs::{create_dir_all, read_to_string, write},
+  io,
+  path::{Path, PathBuf},
+};
+
+/** Bump when the file layout changes, and migrate older versions in `SaveProfile::parse`. */
+pub const SAVE_VERSION: u32 = 1;
+static SAVE_FILE: &str = "save.toml";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct LevelProgress {
+  #[serde(default)]
+  pub unlocked: bool,
+  #[serde(default)]
+  pub completed: bool,
+  pub best_health: Option<usize>,
+  /** Fewest towers built in a clear, counting the ones picked up again. */
+  pub fewest_towers: Option<usize>,
+  /** Fastest clear in simulation ticks. */
+  pub fastest_ticks: Option<u64>,
+}
+
+/** Campaign progress and best scores, keyed by level file name. */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SaveProfile {
+  pub version: u32,
+  #[serde(default)]
+  pub levels: BTreeMap<String, LevelProgress>,
+}
+
+impl Default for SaveProfile {
+  fn default() -> Self {
+    SaveProfile {
+      version: SAVE_VERSION,
+      levels: BTreeMap::new(),
+    }
+  }
+}
+
+impl SaveProfile {
+  /**
+   * Loads the profile from the user data directory, or starts a new one if there is none. Fails
+   * on a save file that can not be read, which the caller can replace with a new profile.
+   */
+  pub fn load() -> Result<SaveProfile, String> {
+    match data_path(SAVE_FILE) {
+      Some(path) if path.exists() => SaveProfile::load_from(&path),
+      _ => Ok(SaveProfile::default()),
+    }
+  }
+  pub fn save(&self) -> Result<(), String> {
+    match data_path(SAVE_FILE) {
+      Some(path) => self
+        .save_to(&path)
+        .map_err(|e| format!("{}: {}", path.display(), e)),
+      None => Ok(()),
+    }
+  }
+
+  pub fn load_from(path: &Path) -> Result<SaveProfile, String> {
+    let text = read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    SaveProfile::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+  }
+  pub fn save_to(&self, path: &Path) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+      create_dir_all(dir)?;
+    }
+    //`io::Error::other` needs Rust 1.74.
+    #[allow(clippy::io_other_error)]
+    let text =
+      toml::to_string(self).map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+    write(path, text)
+  }
+
+  pub fn parse(text: &str) -> Result<SaveProfile, String> {
+    let profile: SaveProfile = toml::from_str(text).map_err(|e| e.to_string())?;
+    if profile.version > SAVE_VERSION {
+      return Err(format!(
+        "version {} is newer than this build understands ({})",
+        profile.version, SAVE_VERSION
+      ));
+    }
+    Ok(SaveProfile {
+      version: SAVE_VERSION,
+      ..profile
+    })
+  }
+
+  pub fn get(&self, level: &str) -> Option<&LevelProgress> {
+    self.levels.get(level)
+  }
+  pub fn completed(&self) -> HashSet<String> {
+    self
+      .levels
+      .iter()
+      .filter(|(_, p)| p.completed)
+      .map(|(name, _)| name.clone())
+      .collect()
+  }
+  pub fn is_unlocked(&self, level: &str) -> bool {
+    self.get(level).map(|p| p.unlocked).unwrap_or(false)
+  }
+  pub fn unlock(&mut self, level: &str) {
+    self.levels.entry(level.to_owned()).or_default().unlocked = true;
+  }
+
+  /** Marks `level` completed and keeps the best of each score. */
+  pub fn record_win(&mut self, level: &str, health: usize, towers: usize, ticks: u64) {
+    let progress = self.levels.entry(level.to_owned()).or_default();
+    progress.unlocked = true;
+    progress.completed = true;
+    progress.best_health = Some(progress.best_health.map_or(health, |h| h.max(health)));
+    progress.fewest_towers = Some(progress.fewest_towers.map_or(towers, |t| t.min(towers)));
+    progress.fastest_ticks = Some(progress.fastest_ticks.map_or(ticks, |t| t.min(ticks)));
+  }
+}
+
+/** `<user data dir>/tddown/<file>`, following each platform's convention. */
+pub fn data_path(file: &str) -> Option<PathBuf> {
+  let data_dir = if cfg!(target_os = "windows") {
+    env::var_os("APPDATA").map(PathBuf::from)
+  } else if cfg!(target_os = "macos") {
+    env::var_os("HOME").map(|home| Path::new(&home).join("Library/Application Support"))
+  } else {
+    env::var_os("XDG_DATA_HOME")
+      .map(PathBuf::from)
+      .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".local/share")))
+  };
+  data_dir.map(|dir| dir.join("tddown").join(file))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn keeps_best_scores() {
+    let mut profile = SaveProfile::default();
+    profile.record_win("level1", 20, 4, 900);
+    profile.record_win("level1", 10, 2, 1200);
+    assert_eq!(
+      profile.get("level1"),
+      Some(&LevelProgress {
+        unlocked: true,
+        completed: true,
+        best_health: Some(20),
+        fewest_towers: Some(2),
+        fastest_ticks: Some(900),
+      })
+    );
+  }
+
+  #[test]
+  fn file_round_trip() {
+    let mut profile = SaveProfile::default();
+    profile.record_win("level1", 20, 4, 900);
+    profile.unlock("level2");
+
+    let path = env::temp_dir()
+      .join(format!("tddown-save-{}", std::process::id()))
+      .join(SAVE_FILE);
+    profile.save_to(&path).unwrap();
+    assert_eq!(SaveProfile::load_from(&path), Ok(profile));
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+  }
+
+  #[test]
+  fn rejects_newer_versions() {
+    let text = format!("version = {}\n", SAVE_VERSION + 1);
+    assert!(SaveProfile::parse(&text).is_err());
+  }
+}
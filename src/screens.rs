@@ -0,0 +1,151 @@
+use macroquad::{
+  prelude::{is_mouse_button_released, mouse_position, Color, MouseButton, WHITE, YELLOW},
+  shapes::draw_rectangle,
+  text::{draw_text, measure_text},
+  window::{screen_height, screen_width},
+};
+
+const TITLE_SIZE: u16 = 64;
+const ITEM_SIZE: u16 = 32;
+const ITEM_SPACING: f32 = 44.0;
+const COMPACT_ITEM_SIZE: u16 = 24;
+const COMPACT_ITEM_SPACING: f32 = 28.0;
+const DIM_COLOR: Color = Color::new(0.0, 0.0, 0.0, 0.6);
+const MUTED_COLOR: Color = Color::new(0.6, 0.6, 0.6, 1.0);
+/** Seconds a notice stays on screen. */
+const NOTICE_TIME: f32 = 4.0;
+
+fn centered(text: &str, y: f32, size: u16, color: Color) {
+  let dims = measure_text(text, None, size, 1.0);
+  draw_text(
+    text,
+    (screen_width() - dims.width) / 2.0,
+    y,
+    size as f32,
+    color,
+  );
+}
+
+/** Darkens whatever was drawn before, used for overlays on top of the level. */
+pub fn dim() {
+  draw_rectangle(0.0, 0.0, screen_width(), screen_height(), DIM_COLOR);
+}
+
+/** Draws a heading with lines of smaller text below it. */
+pub fn message(title: &str, lines: &[&str]) {
+  let mut y = screen_height() / 3.0;
+  centered(title, y, TITLE_SIZE, WHITE);
+  for line in lines {
+    y += ITEM_SPACING;
+    centered(line, y, ITEM_SIZE, MUTED_COLOR);
+  }
+}
+
+/** Shows why the game could not start, wrapped to fit the window, and what `quit` keys close it. */
+pub fn error(title: &str, text: &str, quit: &str) {
+  let max_width = screen_width() * 0.8;
+  let mut lines: Vec<String> = Vec::new();
+  let mut line = String::new();
+  for word in text.split_whitespace() {
+    let candidate = if line.is_empty() {
+      word.to_owned()
+    } else {
+      format!("{} {}", line, word)
+    };
+    if !line.is_empty() && measure_text(&candidate, None, ITEM_SIZE, 1.0).width > max_width {
+      lines.push(line);
+      line = word.to_owned();
+    } else {
+      line = candidate;
+    }
+  }
+  lines.push(line);
+  lines.push(String::new());
+  lines.push(format!("{}: quit", quit));
+
+  let lines: Vec<&str> = lines.iter().map(String::as_str).collect();
+  message(title, &lines);
+}
+
+/** Short messages about things that happened off screen, like saving a file, shown for a while. */
+#[derive(Default)]
+pub struct Notices {
+  /** Text and seconds left, oldest first. */
+  items: Vec<(String, f32)>,
+}
+
+impl Notices {
+  pub fn push(&mut self, text: String) {
+    self.items.push((text, NOTICE_TIME));
+  }
+  /** Ages the notices by `dt` seconds and draws the ones left along the bottom of the window. */
+  pub fn update(&mut self, dt: f32) {
+    for item in self.items.iter_mut() {
+      item.1 -= dt;
+    }
+    self.items.retain(|(_, left)| *left > 0.0);
+    let mut y = screen_height() - ITEM_SPACING * self.items.len() as f32;
+    for (text, _) in &self.items {
+      y += ITEM_SPACING;
+      let dims = measure_text(text, None, ITEM_SIZE, 1.0);
+      let x = (screen_width() - dims.width) / 2.0;
+      draw_rectangle(
+        x - 8.0,
+        y - ITEM_SPACING + 8.0,
+        dims.width + 16.0,
+        ITEM_SPACING,
+        DIM_COLOR,
+      );
+      draw_text(text, x, y, ITEM_SIZE as f32, YELLOW);
+    }
+  }
+}
+
+pub struct MenuItem {
+  pub label: String,
+  pub enabled: bool,
+}
+
+/** Draws a vertical list of items below a heading. Returns the index of a clicked item. */
+pub fn menu(title: &str, items: &[MenuItem]) -> Option<usize> {
+  menu_at(title, items, screen_height() / 4.0, ITEM_SIZE, ITEM_SPACING)
+}
+
+/** `menu` with smaller, tighter items, for lists too long for the screen otherwise. */
+pub fn menu_compact(title: &str, items: &[MenuItem]) -> Option<usize> {
+  menu_at(
+    title,
+    items,
+    TITLE_SIZE as f32,
+    COMPACT_ITEM_SIZE,
+    COMPACT_ITEM_SPACING,
+  )
+}
+
+fn menu_at(title: &str, items: &[MenuItem], top: f32, size: u16, spacing: f32) -> Option<usize> {
+  centered(title, top, TITLE_SIZE, WHITE);
+  let first = top + 2.0 * spacing;
+  //Lists too long for the window wrap into more columns.
+  let per_column = (((screen_height() - first) / spacing) as usize).max(1);
+  let columns = (items.len().max(1) - 1) / per_column + 1;
+  let column_width = screen_width() / columns as f32;
+
+  let (mx, my) = mouse_position();
+  let mut clicked = None;
+  for (i, item) in items.iter().enumerate() {
+    let y = first + (i % per_column) as f32 * spacing;
+    let dims = measure_text(&item.label, None, size, 1.0);
+    let x = column_width * (i / per_column) as f32 + (column_width - dims.width) / 2.0;
+    let hovered = mx > x && mx < x + dims.width && my > y - dims.offset_y && my < y;
+    let color = match (item.enabled, hovered) {
+      (false, _) => MUTED_COLOR,
+      (true, true) => YELLOW,
+      (true, false) => WHITE,
+    };
+    draw_text(&item.label, x, y, size as f32, color);
+    if item.enabled && hovered && is_mouse_button_released(MouseButton::Left) {
+      clicked = Some(i);
+    }
+  }
+  clicked
+}
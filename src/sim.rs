@@ -0,0 +1,712 @@
+use crate::{
+  effects::{damage_enemies, index_effects, spawn_effect, Effect, EffectKind, Effects},
+  enemy::{Enemy, Facing, DEFAULT_ENEMY},
+  input::Command,
+  level::Level,
+  replay::Replay,
+  spatial::SpatialHash,
+  spawner::spawn,
+  tower::Towers,
+  wrld::World,
+};
+
+/** Length of one simulation tick in seconds of game time. */
+pub const TICK: f32 = 1.0 / 60.0;
+/** Upper bound of ticks run for one rendered frame, so a slow frame can not snowball. */
+const MAX_TICKS_PER_FRAME: usize = 30;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Outcome {
+  Won,
+  Lost,
+}
+
+/** Something that happened during a tick, for the presentation to react to. */
+#[derive(Debug, PartialEq, Clone)]
+pub enum Event {
+  /** An enemy of type `kind` ran out of health standing at `pos`. */
+  EnemyDied {
+    kind: String,
+    pos: (usize, usize),
+    facing: Facing,
+  },
+}
+
+/** The whole game state. Advances without a window, textures or global input. */
+pub struct Sim {
+  pub wrld: World,
+  pub towers: Towers,
+  pub enemies: Vec<Enemy>,
+  pub effects: Vec<Effects>,
+  /** Where the effects are, rebuilt at the end of every tick. */
+  pub effect_index: SpatialHash<usize>,
+  /** Every command applied so far, ready to be saved as a replay. */
+  pub replay: Replay,
+  /** What happened during the last tick. */
+  pub events: Vec<Event>,
+}
+
+impl Sim {
+  pub fn new(lvl: Level) -> Sim {
+    let replay = Replay::new(&lvl.name, lvl.seed);
+    let wrld = World::new(lvl);
+    let towers = Towers::new(&wrld);
+    Sim {
+      wrld,
+      towers,
+      enemies: Vec::new(),
+      effects: Vec::new(),
+      effect_index: SpatialHash::new(32),
+      replay,
+      events: Vec::new(),
+    }
+  }
+
+  /**
+   * Restarts the simulation on `lvl`, dropping all towers, enemies, effects and recorded
+   * commands.
+   */
+  pub fn set_level(&mut self, lvl: Level) {
+    self.replay = Replay::new(&lvl.name, lvl.seed);
+    self.wrld.set_level(lvl);
+    self.towers = Towers::new(&self.wrld);
+    self.enemies.clear();
+    self.effects.clear();
+    self.effect_index.clear();
+    self.events.clear();
+  }
+
+  /**
+   * Lost at zero health, won once every enemy has spawned and none are left. A level that never
+   * spawns an enemy can not be won, rather than being won on its first tick.
+   */
+  pub fn outcome(&self) -> Option<Outcome> {
+    let spawner = &self.wrld.get_lvl().spawner;
+    if self.wrld.health == 0 {
+      Some(Outcome::Lost)
+    } else if spawner.is_finished() && *spawner.get_spawned_count() > 0 && self.enemies.is_empty() {
+      Some(Outcome::Won)
+    } else {
+      None
+    }
+  }
+
+  pub fn apply(&mut self, command: &Command) {
+    match command {
+      Command::SelectTower(kind) => self.wrld.select_tower_kind(&self.towers, kind),
+      Command::Deselect => self.wrld.selected_tower_type = None,
+      Command::PlaceTower(grid_pos) => self.towers.place(&mut self.wrld, *grid_pos),
+      Command::UpgradeTower(grid_pos) => self.towers.upgrade(&mut self.wrld, *grid_pos),
+      Command::RemoveTower(grid_pos) => self.towers.remove(&mut self.wrld, *grid_pos),
+      Command::SpeedUp => self.wrld.speed_up(),
+      Command::SpeedDown => self.wrld.speed_down(),
+      Command::Spawn => {
+        let enemy = spawn(&mut self.wrld, DEFAULT_ENEMY);
+        self.enemies.push(enemy)
+      }
+    }
+  }
+
+  /** Applies `commands`, then advances the game by one `TICK`. */
+  pub fn tick(&mut self, commands: &[Command]) {
+    for command in commands {
+      self.replay.push(self.wrld.tick, *command);
+      self.apply(command);
+    }
+
+    let dt = TICK;
+    self.events.clear();
+    self.wrld.update(dt, &mut self.enemies, &self.towers);
+    self.towers.collect(&mut self.wrld, &mut self.enemies);
+    damage_enemies(&mut self.effects, &self.effect_index, &mut self.enemies, dt);
+    self.remove_dead();
+    for (kind, pos) in self.towers.update(dt) {
+      spawn_effect(&mut self.effects, kind, pos);
+    }
+
+    let mut effects_to_spawn: Vec<(EffectKind, (usize, usize))> = Vec::new();
+    let wrld = &self.wrld;
+    self.effects.retain_mut(|effect| {
+      let ret = effect.update(wrld);
+      if let Some(spawn) = ret.spawn {
+        effects_to_spawn.push(spawn);
+      }
+      ret.keep
+    });
+
+    for (kind, pos) in effects_to_spawn {
+      spawn_effect(&mut self.effects, kind, pos);
+    }
+    index_effects(&self.effects, &mut self.effect_index);
+  }
+
+  /** Drops the enemies that ran out of health, paying for each one. */
+  fn remove_dead(&mut self) {
+    let (wrld, events) = (&mut self.wrld, &mut self.events);
+    self.enemies.retain(|enemy| {
+      if !enemy.is_dead() {
+        return true;
+      }
+      wrld.reward_kill();
+      events.push(Event::EnemyDied {
+        kind: enemy.get_kind().to_owned(),
+        pos: enemy.pos,
+        facing: enemy.get_facing(),
+      });
+      false
+    });
+  }
+}
+
+/** Turns rendered frame times into a whole number of simulation ticks. */
+pub struct Clock {
+  /** Game time not yet simulated, at most one `TICK` between frames. */
+  acc: f32,
+}
+
+impl Clock {
+  pub fn new() -> Clock {
+    Clock { acc: 0.0 }
+  }
+
+  /** Returns how many ticks to run for a frame of `frame_time` seconds at the given game speed. */
+  pub fn advance(&mut self, frame_time: f32, speed: f32) -> usize {
+    self.acc += frame_time * speed;
+    let ticks = ((self.acc / TICK).floor() as usize).min(MAX_TICKS_PER_FRAME);
+    self.acc = (self.acc - ticks as f32 * TICK).min(TICK);
+    ticks
+  }
+}
+
+impl Default for Clock {
+  fn default() -> Self {
+    Clock::new()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    buildable::Buildable,
+    emath::{grid_pos_to_pos, pos_to_grid_pos},
+    enemy::Pose,
+    level::Level,
+    loading::{parse_level, read_enemy_table, read_level, read_tower_table},
+    physics::{Aabb, Colliders, Hit},
+    rect::Rect,
+    tower::TowerType,
+  };
+  use macroquad::prelude::vec2;
+
+  fn read(name: &str) -> Level {
+    read_level(
+      name,
+      &read_tower_table().unwrap(),
+      &read_enemy_table().unwrap(),
+    )
+    .unwrap()
+  }
+
+  fn enemy_at(grid_pos: (usize, usize)) -> Enemy {
+    enemy_of(DEFAULT_ENEMY, grid_pos)
+  }
+  fn enemy_of(kind: &str, grid_pos: (usize, usize)) -> Enemy {
+    let table = read_enemy_table().unwrap();
+    Enemy::new(kind, table.get(kind).unwrap(), grid_pos_to_pos(&grid_pos))
+  }
+
+  /**
+   * A level drawn as text: `S` spawn, `G` goal, `#` ground, `v` a painted blocker down, `^` a
+   * painted geyser, `.` empty.
+   */
+  fn sim_from(rows: &[&str]) -> Sim {
+    let pixels: Vec<[u8; 4]> = rows
+      .iter()
+      .flat_map(|row| row.chars())
+      .map(|c| match c {
+        'S' => [213, 0, 0, 255],
+        '#' => [0, 200, 0, 255],
+        'v' => [109, 109, 0, 255],
+        'G' => [113, 0, 0, 255],
+        '^' => [200, 0, 200, 255],
+        _ => [255, 255, 255, 255],
+      })
+      .collect();
+    let config = toml::from_str("enemies = []\n[towers]\n").unwrap();
+    Sim::new(
+      parse_level(
+        "test",
+        rows[0].len(),
+        &pixels,
+        config,
+        0,
+        read_tower_table().unwrap(),
+        read_enemy_table().unwrap(),
+      )
+      .unwrap(),
+    )
+  }
+
+  /** The tiles the hitbox of a `kind` enemy starting at `start` touches in `secs` seconds. */
+  fn path(rows: &[&str], kind: &str, start: (usize, usize), secs: f32) -> Vec<(usize, usize)> {
+    let mut sim = sim_from(rows);
+    sim.enemies.push(enemy_of(kind, start));
+    let mut tiles = Vec::new();
+    for _ in 0..(secs / TICK) as usize {
+      sim.tick(&[]);
+      if let Some(enemy) = sim.enemies.first() {
+        let rect = enemy.get_rect();
+        tiles.push(pos_to_grid_pos(&rect.tl()));
+        tiles.push(pos_to_grid_pos(&(rect.right - 1, rect.bottom - 1)));
+      }
+    }
+    tiles
+  }
+
+  #[test]
+  fn runs_level_headless() {
+    let mut sim = Sim::new(read("level1"));
+    let health = sim.wrld.health;
+    for _ in 0..60 * 120 {
+      sim.tick(&[]);
+    }
+    assert_eq!(*sim.wrld.get_lvl().spawner.get_spawned_count(), 25);
+    assert!(sim.wrld.health < health);
+  }
+
+  #[test]
+  fn spans_spawn_their_enemy_kind() {
+    let base = read("level1");
+    let config = toml::from_str(
+      r#"
+        [[enemies]]
+        time = 1
+        count = 1
+        kind = "brute"
+        [[enemies]]
+        time = 1
+        count = 1
+        [towers]
+      "#,
+    )
+    .unwrap();
+    let mut sim = Sim::new(Level::new(
+      "level1",
+      base.width,
+      base.tiles,
+      config,
+      0,
+      base.tower_table,
+      base.enemy_table,
+    ));
+    while sim.enemies.len() < 2 {
+      sim.tick(&[]);
+    }
+    assert_eq!(sim.enemies[0].get_kind(), "brute");
+    assert_eq!(sim.enemies[1].get_kind(), DEFAULT_ENEMY);
+  }
+
+  #[test]
+  fn commands_place_towers() {
+    let mut sim = Sim::new(read("level1"));
+    sim.tick(&[
+      Command::SelectTower(TowerType::Lava),
+      Command::PlaceTower((6, 5)),
+    ]);
+    assert_eq!(sim.towers.get_tower_count(&TowerType::Lava), 1);
+
+    //Blocker up can not be built on a build down tile.
+    sim.tick(&[
+      Command::SelectTower(TowerType::BlockerUp),
+      Command::PlaceTower((2, 6)),
+    ]);
+    assert_eq!(sim.towers.get_tower_count(&TowerType::BlockerUp), 0);
+  }
+
+  #[test]
+  fn removing_refunds_supply() {
+    let mut sim = Sim::new(read("level1"));
+    let place = [
+      Command::SelectTower(TowerType::Lava),
+      Command::PlaceTower((6, 5)),
+    ];
+    sim.tick(&place);
+    sim.tick(&[Command::RemoveTower((6, 5))]);
+    assert_eq!(sim.towers.get_tower_count(&TowerType::Lava), 0);
+    //The lava tower drops right away when built, but not once picked up.
+    for _ in 0..60 * 5 {
+      sim.tick(&[]);
+    }
+    assert!(sim.effects.is_empty());
+
+    //The tile is free again.
+    sim.tick(&place);
+    assert_eq!(sim.towers.get_tower_count(&TowerType::Lava), 1);
+    //Picking towers up does not make for fewer towers used.
+    assert_eq!(sim.towers.get_built_count(), 2);
+    //Removing an empty tile does nothing.
+    sim.tick(&[Command::RemoveTower((7, 5))]);
+    assert_eq!(sim.towers.get_tower_count(&TowerType::Lava), 1);
+
+    let mut lvl = read("level1");
+    lvl.refunds = false;
+    sim.set_level(lvl);
+    sim.tick(&place);
+    sim.tick(&[Command::RemoveTower((6, 5))]);
+    assert_eq!(sim.towers.get_tower_count(&TowerType::Lava), 1);
+  }
+
+  #[test]
+  fn economy_pays_for_towers() {
+    let base = read("level1");
+    let config = toml::from_str(
+      r#"
+        enemies = []
+        [towers]
+        block_down = 1
+        [economy]
+        budget = 100
+        reward = 7
+        [economy.costs]
+        lava = 60
+      "#,
+    )
+    .unwrap();
+    let mut sim = Sim::new(Level::new(
+      "level1",
+      base.width,
+      base.tiles,
+      config,
+      0,
+      base.tower_table,
+      base.enemy_table,
+    ));
+    let blocker_cost = sim
+      .wrld
+      .get_lvl()
+      .tower_table
+      .build_cost(TowerType::BlockerDown);
+    assert_eq!(sim.wrld.money, 100);
+
+    sim.tick(&[
+      Command::SelectTower(TowerType::Lava),
+      Command::PlaceTower((6, 5)),
+    ]);
+    assert_eq!(sim.wrld.money, 40);
+    //Not enough money for a second one, though lava has no supply cap.
+    assert_eq!(sim.wrld.selected_tower_type, None);
+    sim.tick(&[
+      Command::SelectTower(TowerType::Lava),
+      Command::PlaceTower((8, 5)),
+    ]);
+    assert_eq!(sim.towers.get_tower_count(&TowerType::Lava), 1);
+
+    //Blockers cost their default, and the supply still caps them.
+    sim.tick(&[
+      Command::SelectTower(TowerType::BlockerDown),
+      Command::PlaceTower((2, 6)),
+    ]);
+    assert_eq!(sim.wrld.money, 40 - blocker_cost);
+    assert_eq!(sim.wrld.selected_tower_type, None);
+
+    sim.tick(&[Command::RemoveTower((6, 5))]);
+    assert_eq!(sim.wrld.money, 100 - blocker_cost);
+
+    sim.tick(&[
+      Command::SelectTower(TowerType::Collector),
+      Command::PlaceTower((7, 9)),
+    ]);
+    let money = sim.wrld.money;
+    sim.enemies.push(enemy_at((7, 8)));
+    sim.tick(&[]);
+    assert_eq!(sim.wrld.money, money + 7);
+  }
+
+  #[test]
+  fn upgrades_use_the_tower_table() {
+    let mut lvl = read("level1");
+    lvl.upgrades = 1;
+    let mut sim = Sim::new(lvl);
+    sim.tick(&[
+      Command::SelectTower(TowerType::BlockerDown),
+      Command::PlaceTower((2, 6)),
+    ]);
+    //Tier 2 reaches a tile further down.
+    let below = Rect::new(2 * 32 + 4, 9 * 32 + 4, 2 * 32 + 8, 9 * 32 + 8);
+    assert!(sim.towers.get_collided_tower(&below).is_none());
+    sim.tick(&[Command::UpgradeTower((2, 6))]);
+    assert!(sim.towers.get_collided_tower(&below).is_some());
+    assert_eq!(sim.wrld.upgrades, 0);
+
+    //Out of upgrades.
+    sim.tick(&[Command::UpgradeTower((2, 6))]);
+    let tower = sim.towers.get_tower(&sim.wrld, (2, 6)).unwrap();
+    assert_eq!(tower.get_tier(), 1);
+
+    //Picking the tower up gives the upgrade back.
+    sim.tick(&[Command::RemoveTower((2, 6))]);
+    assert_eq!(sim.wrld.upgrades, 1);
+    assert!(sim.towers.get_collided_tower(&below).is_none());
+  }
+
+  #[test]
+  fn placed_blockers_collide() {
+    let mut sim = Sim::new(read("level1"));
+    sim.tick(&[
+      Command::SelectTower(TowerType::BlockerDown),
+      Command::PlaceTower((2, 6)),
+    ]);
+    //A blocker down covers the two cells below its tile.
+    let below = Rect::new(2 * 32 + 4, 8 * 32, 2 * 32 + 8, 8 * 32 + 4);
+    assert!(sim.towers.get_collided_tower(&below).is_some());
+    let elsewhere = Rect::new(10 * 32, 20 * 32, 10 * 32 + 4, 20 * 32 + 4);
+    assert!(sim.towers.get_collided_tower(&elsewhere).is_none());
+  }
+
+  #[test]
+  fn collectors_catch_until_full() {
+    let mut lvl = read("level1");
+    //Level 1 has no collectors in its supply.
+    lvl.twr_supply[TowerType::Collector] = 1;
+    let capacity = lvl.tower_table.def(TowerType::Collector).capacity.unwrap();
+    let mut sim = Sim::new(lvl);
+    sim.tick(&[
+      Command::SelectTower(TowerType::Collector),
+      Command::PlaceTower((7, 9)),
+    ]);
+    assert_eq!(sim.towers.get_tower_count(&TowerType::Collector), 1);
+
+    let health = sim.wrld.health;
+    //An enemy walking along the floor above the build tile.
+    sim.enemies.push(enemy_at((7, 8)));
+    sim.tick(&[]);
+    assert!(sim.enemies.is_empty());
+    assert_eq!(sim.towers.get_caught_count(), 1);
+    assert_eq!(sim.wrld.health, health);
+
+    for _ in 1..capacity + 1 {
+      sim.enemies.push(enemy_at((7, 8)));
+      sim.tick(&[]);
+    }
+    assert_eq!(sim.towers.get_caught_count(), capacity);
+    assert_eq!(sim.enemies.len(), 1);
+  }
+
+  #[test]
+  fn effects_hurt_until_death() {
+    let mut sim = Sim::new(read("level1"));
+    sim.enemies.push(enemy_at((7, 8)));
+    let health = sim.enemies[0].get_health();
+    //A splash burns for as long as the enemy stands in it.
+    spawn_effect(&mut sim.effects, EffectKind::LavaSplash, sim.enemies[0].pos);
+    index_effects(&sim.effects, &mut sim.effect_index);
+    sim.tick(&[]);
+    let burnt = sim.enemies[0].get_health();
+    assert!(burnt < health && burnt > health - 1.0);
+    assert!(sim.events.is_empty());
+
+    //A drop is used up on its first hit, and splashes.
+    spawn_effect(
+      &mut sim.effects,
+      EffectKind::LavaDrop,
+      grid_pos_to_pos(&(7, 8)),
+    );
+    index_effects(&sim.effects, &mut sim.effect_index);
+    sim.tick(&[]);
+    assert!(sim.enemies.is_empty());
+    assert!(matches!(
+      &sim.events[..],
+      [Event::EnemyDied { kind, .. }] if kind == DEFAULT_ENEMY
+    ));
+    assert!(sim
+      .effects
+      .iter()
+      .all(|e| e.get_kind() == &EffectKind::LavaSplash));
+  }
+
+  #[test]
+  fn jumpers_and_floaters_cross_gaps() {
+    let gap = ["S.......", "#......#", "###.####"];
+    assert!(path(&gap, DEFAULT_ENEMY, (5, 1), 3.0)
+      .iter()
+      .any(|p| p.1 > 1));
+    for kind in ["hopper", "ghost"] {
+      assert!(
+        path(&gap, kind, (5, 1), 3.0).iter().all(|p| p.1 == 1),
+        "{}",
+        kind
+      );
+    }
+  }
+
+  #[test]
+  fn floaters_drift_to_the_goal() {
+    let below = ["S......", ".......", "......G", "#######"];
+    let above = [".......", "......G", "S......", "#######"];
+    for (rows, start) in [(below, (1, 0)), (above, (1, 2))] {
+      let mut sim = sim_from(&rows);
+      let health = sim.wrld.health;
+      sim.enemies.push(enemy_of("ghost", start));
+      for _ in 0..(10.0 / TICK) as usize {
+        sim.tick(&[]);
+      }
+      assert!(sim.enemies.is_empty());
+      assert!(sim.wrld.health < health);
+    }
+  }
+
+  #[test]
+  fn climbers_and_diggers_get_past_walls() {
+    let step = ["S.......", "#......#", "###....#", "########"];
+    assert!(path(&step, DEFAULT_ENEMY, (5, 2), 3.0)
+      .iter()
+      .all(|p| p.1 == 2));
+    assert!(path(&step, "climber", (5, 2), 3.0).iter().any(|p| p.1 == 1));
+
+    //The blocker down painted on (2, 1) walls off the tiles below it.
+    let wall = ["S.......", "#.v....#", "#......#", "#......#", "########"];
+    assert!(path(&wall, DEFAULT_ENEMY, (5, 3), 6.0)
+      .iter()
+      .all(|p| p.0 > 2));
+    assert!(path(&wall, "digger", (5, 3), 6.0).iter().any(|p| p.0 < 2));
+  }
+
+  #[test]
+  fn sweeps_stop_at_walls_and_level_edges() {
+    let sim = sim_from(&["S.......", "......#.", "########"]);
+    let colliders = Colliders::new(&sim.wrld, Some(&sim.towers));
+    let enemy = Aabb::new(vec2(35.0, 44.0), vec2(59.0, 64.0));
+    //However far the move, the wall on (6, 1) stops it.
+    let sweep = colliders.sweep(&enemy, vec2(10_000.0, 0.0));
+    assert_eq!(sweep.moved, vec2(192.0 - 59.0, 0.0));
+    assert!(matches!(
+      sweep.contact.map(|c| c.hit),
+      Some(Hit::Tile {
+        grid_pos: (6, 1),
+        ..
+      })
+    ));
+    //The level has no border, its edge stops it all the same.
+    let sweep = colliders.sweep(&enemy, vec2(-10_000.0, 0.0));
+    assert_eq!(sweep.moved, vec2(-35.0, 0.0));
+    assert_eq!(sweep.contact.map(|c| c.hit), Some(Hit::Edge));
+
+    //Walkers turn at the edge instead of leaving the level.
+    let tiles = path(
+      &["S.......", "......#.", "########"],
+      DEFAULT_ENEMY,
+      (2, 1),
+      4.0,
+    );
+    assert!(tiles.iter().any(|p| p.0 == 0));
+    assert!(tiles.iter().all(|p| p.0 < 6));
+  }
+
+  #[test]
+  fn poses_follow_what_enemies_do() {
+    let mut sim = sim_from(&["S.......", "........", "#......#", "########"]);
+    sim.enemies.push(enemy_at((5, 0)));
+    let mut poses: Vec<Pose> = Vec::new();
+    for _ in 0..(4.0 / TICK) as usize {
+      sim.tick(&[]);
+      let pose = sim.enemies[0].get_pose();
+      if poses.last() != Some(&pose) {
+        poses.push(pose);
+      }
+    }
+    //Drops in, walks left into the wall and turns around.
+    assert_eq!(poses[..4], [Pose::Fall, Pose::Walk, Pose::Turn, Pose::Walk]);
+    assert_eq!(sim.enemies[0].get_facing(), Facing::Right);
+  }
+
+  #[test]
+  fn painted_towers_are_free() {
+    let (w, s, b) = ([255, 255, 255, 255], [213, 0, 0, 255], [200, 200, 0, 255]);
+    let pixels = [s, w, w, w, w, w, w, w, w, w, b, w];
+    let config = toml::from_str("enemies = []\n[towers]\n").unwrap();
+    let sim = Sim::new(
+      parse_level(
+        "test",
+        3,
+        &pixels,
+        config,
+        0,
+        read_tower_table().unwrap(),
+        read_enemy_table().unwrap(),
+      )
+      .unwrap(),
+    );
+
+    //A blocker up on (1, 3) covers the two tiles above it.
+    assert!(sim
+      .towers
+      .get_collided_tower(&Rect::new(40, 40, 44, 44))
+      .is_some());
+    assert_eq!(sim.towers.get_tower_count(&TowerType::BlockerUp), 0);
+    assert_eq!(sim.towers.get_total_count(), 0);
+  }
+
+  #[test]
+  fn painted_geysers_burn_what_walks_over_them() {
+    let mut sim = sim_from(&["S.......", "........", "###^####"]);
+    assert_eq!(sim.towers.get_tower_count(&TowerType::Geyser), 0);
+    sim.enemies.push(enemy_at((6, 1)));
+    let health = sim.enemies[0].get_health();
+    for _ in 0..(3.0 / TICK) as usize {
+      sim.tick(&[]);
+    }
+    assert!(sim.enemies[0].get_health() < health);
+  }
+
+  #[test]
+  fn same_seed_same_outcome() {
+    let run = || {
+      let mut sim = Sim::new(read("level1"));
+      for tick in 0..60 * 30 {
+        let commands = match tick {
+          10 => vec![
+            Command::SelectTower(TowerType::Lava),
+            Command::PlaceTower((6, 5)),
+          ],
+          200 => vec![Command::Spawn],
+          _ => vec![],
+        };
+        sim.tick(&commands);
+      }
+      let positions: Vec<_> = sim.enemies.iter().map(|e| e.pos).collect();
+      (positions, sim.wrld.health, sim.effects.len())
+    };
+    assert_eq!(run(), run());
+  }
+
+  #[test]
+  fn clock_scales_ticks_with_speed() {
+    let mut clock = Clock::new();
+    assert_eq!(clock.advance(10.5 * TICK, 1.0), 10);
+    assert_eq!(clock.advance(10.0 * TICK, 2.0), 20);
+    assert_eq!(clock.advance(TICK, 100.0), MAX_TICKS_PER_FRAME);
+  }
+
+  #[test]
+  fn outcome_after_all_enemies() {
+    let mut sim = Sim::new(read("level1"));
+    while sim.outcome().is_none() {
+      sim.tick(&[]);
+    }
+    assert_eq!(sim.outcome(), Some(Outcome::Won));
+    assert_eq!(*sim.wrld.get_lvl().spawner.get_spawned_count(), 25);
+
+    sim.set_level(read("level1"));
+    sim.wrld.health = 3;
+    while sim.outcome().is_none() {
+      sim.tick(&[]);
+    }
+    assert_eq!(sim.outcome(), Some(Outcome::Lost));
+    assert_eq!(sim.wrld.health, 0);
+
+    let mut empty = sim_from(&["S.", "##"]);
+    empty.tick(&[]);
+    assert_eq!(empty.outcome(), None);
+  }
+}
@@ -0,0 +1,89 @@
+use std::collections::HashMap;
+
+use crate::rect::Rect;
+
+/**
+ * Ids of colliders bucketed by the grid cells their rect covers, so a query only looks at the
+ * colliders near it. Rects touching a cell edge count as inside, like `Rect::intersecting`.
+ */
+pub struct SpatialHash<T> {
+  cell_size: usize,
+  cells: HashMap<(usize, usize), Vec<T>>,
+}
+
+impl<T: Copy + PartialEq> SpatialHash<T> {
+  pub fn new(cell_size: usize) -> SpatialHash<T> {
+    SpatialHash {
+      cell_size,
+      cells: HashMap::new(),
+    }
+  }
+
+  fn covered(&self, rect: &Rect) -> impl Iterator<Item = (usize, usize)> {
+    let (left, right) = (rect.left / self.cell_size, rect.right / self.cell_size);
+    let (top, bottom) = (rect.top / self.cell_size, rect.bottom / self.cell_size);
+    (top..=bottom).flat_map(move |y| (left..=right).map(move |x| (x, y)))
+  }
+
+  pub fn clear(&mut self) {
+    self.cells.clear();
+  }
+  pub fn insert(&mut self, id: T, rect: &Rect) {
+    for cell in self.covered(rect) {
+      self.cells.entry(cell).or_default().push(id);
+    }
+  }
+  /** `rect` must be the one `id` was inserted with. */
+  pub fn remove(&mut self, id: T, rect: &Rect) {
+    for cell in self.covered(rect) {
+      if let Some(ids) = self.cells.get_mut(&cell) {
+        ids.retain(|i| *i != id);
+        if ids.is_empty() {
+          self.cells.remove(&cell);
+        }
+      }
+    }
+  }
+
+  /** Ids in the cells `rect` covers, each once. Callers still test the actual rects. */
+  pub fn query(&self, rect: &Rect) -> Vec<T> {
+    let mut found: Vec<T> = Vec::new();
+    for cell in self.covered(rect) {
+      for id in self.cells.get(&cell).into_iter().flatten() {
+        if !found.contains(id) {
+          found.push(*id);
+        }
+      }
+    }
+    found
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn finds_only_nearby_ids() {
+    let mut hash = SpatialHash::new(32);
+    hash.insert(1, &Rect::new(0, 0, 32, 32));
+    hash.insert(2, &Rect::new(200, 200, 232, 296));
+    assert_eq!(hash.query(&Rect::new(10, 10, 20, 20)), vec![1]);
+    assert_eq!(hash.query(&Rect::new(210, 250, 220, 260)), vec![2]);
+    assert!(hash.query(&Rect::new(100, 100, 110, 110)).is_empty());
+    //Touching edges share a cell.
+    assert_eq!(hash.query(&Rect::new(32, 32, 40, 40)), vec![1]);
+  }
+
+  #[test]
+  fn removes_ids() {
+    let mut hash = SpatialHash::new(32);
+    let rect = Rect::new(0, 0, 64, 64);
+    hash.insert(1, &rect);
+    hash.insert(2, &rect);
+    hash.remove(1, &rect);
+    assert_eq!(hash.query(&rect), vec![2]);
+    hash.remove(2, &rect);
+    assert!(hash.cells.is_empty());
+  }
+}
@@ -1,28 +1,39 @@
-use rand::{seq::SliceRandom, thread_rng};
 use serde::Deserialize;
 
-use crate::{enemy::Enemy, rect::Rect, wrld::World};
+use crate::{
+  enemy::{Enemy, DEFAULT_ENEMY},
+  wrld::World,
+};
 
 #[derive(Deserialize, Debug, Clone)]
 pub struct SpawnSpanSerialized {
   pub time: f32,
   pub count: isize,
+  /** Enemy type spawned, an id of `assets/enemies.toml`. */
+  #[serde(default = "default_kind")]
+  pub kind: String,
+}
+
+fn default_kind() -> String {
+  DEFAULT_ENEMY.to_owned()
 }
 
 #[derive(Debug, Clone)]
 pub struct SpawnSpan {
   pub time: f32,
   pub count: usize,
+  pub kind: String,
   spawned: usize,
   timer: f32,
   time_between_spawns: f32,
 }
 
 impl SpawnSpan {
-  pub fn new(time: f32, count: usize) -> SpawnSpan {
+  pub fn new(time: f32, count: usize, kind: &str) -> SpawnSpan {
     SpawnSpan {
       time,
       count,
+      kind: kind.to_owned(),
       spawned: 0,
       timer: 0.0,
       time_between_spawns: count as f32 / time,
@@ -56,25 +67,31 @@ pub struct Spawner {
 impl Spawner {
   pub fn new(spans: Vec<SpawnSpan>) -> Spawner {
     Spawner {
-      total_to_spawn: spans.iter().fold(0, |acc, s| acc + s.count),
+      total_to_spawn: spans.iter().map(|s| s.count).sum(),
       spans,
       current_span_index: 0,
       spawned: 0,
     }
   }
 
-  pub fn check_spawn(&mut self, dt: f32) -> bool {
-    if self.current_span_index >= self.spans.len() {
-      return false;
-    }
-    let (should_spawn, is_finished) = self.spans[self.current_span_index].spawn(dt);
+  /** The kind of enemy to spawn this tick, if any. */
+  pub fn check_spawn(&mut self, dt: f32) -> Option<String> {
+    let span = self.spans.get_mut(self.current_span_index)?;
+    let (should_spawn, is_finished) = span.spawn(dt);
+    let kind = span.kind.clone();
     if is_finished {
       self.current_span_index += 1;
     }
     if should_spawn {
       self.spawned += 1;
+      return Some(kind);
     }
-    should_spawn
+    None
+  }
+
+  /** True once every span has spawned all of its enemies. */
+  pub fn is_finished(&self) -> bool {
+    self.current_span_index >= self.spans.len()
   }
 
   pub fn get_total_to_spawn(&self) -> &usize {
@@ -91,13 +108,13 @@ impl Spawner {
   }
 }
 
-pub fn spawn(wrld: &World) -> Enemy {
-  let spawns = wrld.get_spawns();
-  let spawn = spawns.choose(&mut thread_rng()).unwrap().to_owned();
-  Enemy::new(
-    spawn,
-    Rect::new(3, 12, 27, 32),
-    Rect::new(3, 12, 27, 32),
-    wrld.textures.enemy,
-  )
+/** Loaded levels are checked to only name kinds the enemy table has. */
+pub fn spawn(wrld: &mut World, kind: &str) -> Enemy {
+  let spawn = wrld.pick_spawn();
+  let def = wrld
+    .get_lvl()
+    .enemy_table
+    .get(kind)
+    .expect("Spawn span names an unknown enemy.");
+  Enemy::new(kind, def, spawn)
 }
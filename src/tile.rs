@@ -1,13 +1,13 @@
 use macroquad::{
-  prelude::{vec2, GREEN, PURPLE, WHITE},
-  texture::{draw_texture_ex, DrawTextureParams, Texture2D},
+  prelude::{vec2, Color, GREEN, PURPLE, WHITE},
+  texture::DrawTextureParams,
 };
 
 use crate::{
   emath::grid_pos_to_pos,
+  loading::Textures,
   rect::{Collidable, Rect},
-  tower::{Dir, Towers},
-  wrld::World,
+  tower::{Dir, TowerType},
 };
 
 #[derive(Debug, PartialEq, Clone, Copy)]
@@ -34,10 +34,27 @@ pub enum TileType {
   TurretUp,
 }
 
+impl TileType {
+  /**
+   * The tower a level designer painted on this tile, built for free when the level starts.
+   * Turrets are the effect towers: a lava tower hanging down, a geyser standing up.
+   */
+  pub fn placed_tower(&self) -> Option<TowerType> {
+    match self {
+      TileType::BlockerDown => Some(TowerType::BlockerDown),
+      TileType::BlockerUp => Some(TowerType::BlockerUp),
+      TileType::TurretDown => Some(TowerType::Lava),
+      TileType::TurretUp => Some(TowerType::Geyser),
+      _ => None,
+    }
+  }
+}
+
 #[derive(Clone, Copy)]
 pub struct BaseTile {
   pub kind: TileType,
-  pub texture: Texture2D,
+  /** Picks between the texture variants of a kind, e.g. the background tiles. */
+  pub variant: u8,
   pub size: (usize, usize),
   pub grid_pos: (usize, usize),
   pub index: usize,
@@ -46,7 +63,7 @@ impl BaseTile {
   pub fn from_other(other: &BaseTile) -> BaseTile {
     BaseTile {
       kind: other.kind,
-      texture: other.texture,
+      variant: other.variant,
       size: other.size,
       grid_pos: other.grid_pos,
       index: other.index,
@@ -91,12 +108,19 @@ impl Tile {
   pub fn kind(&self) -> &TileType {
     &self.base.kind
   }
+  /** Only empty tiles can be walked or fallen through. */
+  pub fn is_passable(&self) -> bool {
+    self.passable
+  }
   pub fn pos(&self) -> (usize, usize) {
     grid_pos_to_pos(&self.base.grid_pos)
   }
   pub fn grid_pos(&self) -> (usize, usize) {
     self.base.grid_pos
   }
+  pub fn variant(&self) -> u8 {
+    self.base.variant
+  }
 
   pub fn debug_draw(&self) {
     if !self.passable {
@@ -104,29 +128,43 @@ impl Tile {
     }
   }
 
-  pub fn draw(&self, wrld: &World) {
-    let color = if let Some(selected_kind) = wrld.selected_tower_type {
-      match (self.kind(), Towers::tower_dir(&selected_kind)) {
-        (TileType::BuildDown, Dir::Down) => GREEN,
-        (TileType::BuildUp, Dir::Up) => GREEN,
-        _ => WHITE,
-      }
-    } else {
-      WHITE
-    };
-    draw_texture_ex(
-      self.base.texture,
-      ((self.base.grid_pos.0 * 32) as f32 + wrld.scroll_pos.x) * wrld.zoom,
-      ((self.base.grid_pos.1 * 32) as f32 + wrld.scroll_pos.y) * wrld.zoom,
-      // Color::from_rgba(20, 20, 20, 255),
+  /** Build tiles that take a tower built in direction `build_dir` are drawn highlighted. */
+  pub fn is_highlighted(&self, build_dir: Option<Dir>) -> bool {
+    matches!(
+      (self.kind(), build_dir),
+      (TileType::BuildDown, Some(Dir::Down)) | (TileType::BuildUp, Some(Dir::Up))
+    )
+  }
+
+  /** Draws the tile with its top left corner at `(x, y)`, `grid_size` pixels per grid cell. */
+  pub fn draw_at(&self, texs: &Textures, x: f32, y: f32, grid_size: f32, color: Color) {
+    texs.tile(self.kind(), self.variant()).draw(
+      x,
+      y,
       color,
       DrawTextureParams {
         dest_size: Some(vec2(
-          wrld.grid_size * self.base.size.0 as f32,
-          wrld.grid_size * self.base.size.1 as f32,
+          grid_size * self.base.size.0 as f32,
+          grid_size * self.base.size.1 as f32,
         )),
         ..Default::default()
       },
     )
   }
+
+  /** Draws in level pixels, under the camera. */
+  pub fn draw(&self, texs: &Textures, build_dir: Option<Dir>) {
+    let color = if self.is_highlighted(build_dir) {
+      GREEN
+    } else {
+      WHITE
+    };
+    self.draw_at(
+      texs,
+      (self.base.grid_pos.0 * 32) as f32,
+      (self.base.grid_pos.1 * 32) as f32,
+      32.0,
+      color,
+    )
+  }
 }
@@ -1,254 +1,187 @@
 use enum_map::{Enum, EnumMap};
-use macroquad::{
-  prelude::{is_mouse_button_released, vec2, MouseButton, PINK, RED, WHITE},
-  texture::{draw_texture_ex, DrawTextureParams, Texture2D},
-  time::get_frame_time,
-};
-use std::{fmt::Display, iter::repeat_with};
+use serde::Deserialize;
+use std::iter::repeat_with;
 
 use crate::{
-  deb::DEBUG,
+  buildable::{Buildable, Tower},
+  camera::Camera,
   effects::EffectKind,
-  emath::grid_pos_to_pos,
+  enemy::Enemy,
   loading::Textures,
   rect::{Collidable, Rect},
+  spatial::SpatialHash,
   tile::TileType,
   wrld::World,
 };
 
+/** Tower kinds. What each one does is defined in `assets/towers.toml`, keyed by `id`. */
 #[derive(Debug, PartialEq, Clone, Copy, Enum)]
 pub enum TowerType {
   BlockerDown,
   BlockerUp,
   Lava,
+  /**
+   * Built on a build up tile, stands in the tile above it like a collector. Spits its effect
+   * into that tile, burning the enemies walking through.
+   */
+  Geyser,
+  /**
+   * Built on a build up tile, sits in the tile above it. Catches every enemy whose hitbox
+   * touches its trigger, walking or falling, at the end of the tick. Caught enemies are gone
+   * without costing health. Never blocks; once it holds its capacity it is full and enemies
+   * pass through.
+   */
   Collector,
 }
-#[derive(Debug, PartialEq, Clone, Copy)]
+impl TowerType {
+  /** Snake case name, the key of the kind's definition and the prefix of its button sprites. */
+  pub fn id(&self) -> &'static str {
+    match self {
+      TowerType::BlockerDown => "blocker_down",
+      TowerType::BlockerUp => "blocker_up",
+      TowerType::Lava => "lava",
+      TowerType::Geyser => "geyser",
+      TowerType::Collector => "collector",
+    }
+  }
+}
+#[derive(Deserialize, Debug, PartialEq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
 pub enum Dir {
   Up,
   Down,
 }
 
-pub struct FrameDrawing {
-  pub frames: Vec<Texture2D>,
-  pub frame: usize,
-  pub count: usize,
-  pub timer: f32,
-  pub timeout: f32,
+/** `[left, top, right, bottom]` in pixels, relative to the top left corner of the build tile. */
+pub type Offsets = [isize; 4];
+
+pub fn offset_rect(offsets: &Offsets, pos: (usize, usize)) -> Rect {
+  let at = |base: usize, offset: isize| (base as isize + offset).max(0) as usize;
+  Rect::new(
+    at(pos.0, offsets[0]),
+    at(pos.1, offsets[1]),
+    at(pos.0, offsets[2]),
+    at(pos.1, offsets[3]),
+  )
 }
-impl FrameDrawing {
-  pub fn new(frames: Vec<Texture2D>, timer: f32) -> FrameDrawing {
-    FrameDrawing {
-      count: frames.len(),
-      frames,
-      frame: 0,
-      timer,
-      timeout: timer,
-    }
-  }
-  pub fn reset_timer(&mut self) {
-    self.timer = self.timeout;
-  }
-  pub fn is_end(&self) -> bool {
-    self.frame >= self.count - 1
-  }
-  pub fn step_time(&mut self) -> Texture2D {
-    self.timer -= get_frame_time();
-    if self.timer <= 0.0 {
-      self.reset_timer();
-      self.frame += 1;
+
+/** The part of a tower definition an upgrade tier can change. Left out fields stay as they were. */
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct TowerTier {
+  /** Build price of the first tier, upgrade price of the others. */
+  #[serde(default)]
+  pub cost: usize,
+  pub sprite: Option<String>,
+  pub hitbox: Option<Offsets>,
+  pub draw: Option<Offsets>,
+  pub trigger: Option<Offsets>,
+  pub interval: Option<f32>,
+  /** `[r, g, b]` multiplied into the sprite, so tiers look apart while they share art. */
+  pub tint: Option<[u8; 3]>,
+}
+
+impl TowerTier {
+  /** `self` with the fields `upgrade` sets replaced, at the upgrade's cost. */
+  fn upgraded(&self, upgrade: &TowerTier) -> TowerTier {
+    TowerTier {
+      cost: upgrade.cost,
+      sprite: upgrade.sprite.clone().or_else(|| self.sprite.clone()),
+      hitbox: upgrade.hitbox.or(self.hitbox),
+      draw: upgrade.draw.or(self.draw),
+      trigger: upgrade.trigger.or(self.trigger),
+      interval: upgrade.interval.or(self.interval),
+      tint: upgrade.tint.or(self.tint),
     }
-    self.frames[self.frame % self.count]
-  }
-  pub fn step(&mut self) -> Texture2D {
-    self.frame += 1;
-    self.frames[self.frame % self.count]
   }
 }
 
-struct EffectSpawnData {
-  pub timer: f32,
-  pub time: f32,
-  pub kind: EffectKind,
-  pub pos: (usize, usize),
-}
-impl EffectSpawnData {
-  pub fn reset_timer(&mut self) {
-    self.timer = self.time;
-  }
+/** One entry of `assets/towers.toml`. */
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct TowerDef {
+  /** Build tiles it goes on. */
+  pub direction: Dir,
+  /** Whether enemies bump into the hitbox. */
+  #[serde(default)]
+  pub solid: bool,
+  /** Enemies the trigger catches before the tower is full, no limit if left out. */
+  pub capacity: Option<usize>,
+  /** Spawned every `interval` seconds, the first one right after building. */
+  pub effect: Option<EffectKind>,
+  /** `[x, y]` tile the effect spawns on, relative to the build tile. */
+  #[serde(default)]
+  pub effect_tile: [isize; 2],
+  /** The first tier. */
+  #[serde(flatten)]
+  pub base: TowerTier,
+  /** Upgrades, in order. */
+  #[serde(default)]
+  pub tiers: Vec<TowerTier>,
 }
 
-pub struct Tower {
-  grid_pos: (usize, usize),
-  kind: TowerType,
-  /** Offset and size; in pixels units. */
-  draw_pos: Rect,
-  rect: Rect,
-  texture: Texture2D,
-  trigger: Option<Rect>,
-  atlas: Option<FrameDrawing>,
-  direction: Dir,
-  spawn: Option<EffectSpawnData>,
-  count: usize,
+/** The definitions of every tower kind, with every tier worked out. */
+#[derive(Debug, Clone, PartialEq)]
+pub struct TowerTable {
+  defs: EnumMap<TowerType, TowerDef>,
+  /** Full stats per tier, the first being the base. */
+  tiers: EnumMap<TowerType, Vec<TowerTier>>,
 }
 
-impl Tower {
-  pub fn get_texture(&mut self) -> Texture2D {
-    if let Some(atlas) = &mut self.atlas {
-      atlas.timer -= get_frame_time();
-      if atlas.timer <= 0.0 {
-        atlas.reset_timer();
-        atlas.frame = (atlas.frame + 1) % atlas.count;
+impl TowerTable {
+  /** Fails with the kind and what its definition is missing. */
+  pub fn new(defs: EnumMap<TowerType, TowerDef>) -> Result<TowerTable, (TowerType, String)> {
+    for (kind, def) in &defs {
+      let base = &def.base;
+      let missing = [
+        ("sprite", base.sprite.is_none()),
+        ("hitbox", base.hitbox.is_none()),
+        ("draw", base.draw.is_none()),
+        ("interval", def.effect.is_some() && base.interval.is_none()),
+        (
+          "effect",
+          matches!(kind, TowerType::Lava | TowerType::Geyser) && def.effect.is_none(),
+        ),
+        (
+          "trigger",
+          kind == TowerType::Collector && base.trigger.is_none(),
+        ),
+      ];
+      if let Some((field, _)) = missing.iter().find(|(_, missing)| *missing) {
+        return Err((kind, format!("no {}", field)));
       }
-      return atlas.frames[atlas.frame];
     }
-    self.texture
-  }
-  pub fn draw(&mut self, wrld: &World) {
-    let tl = self.draw_pos.tl();
-    let width = self.draw_pos.width() as f32 * wrld.zoom;
-    let height = self.draw_pos.height() as f32 * wrld.zoom;
-    draw_texture_ex(
-      self.get_texture(),
-      (tl.0 as f32 + wrld.scroll_pos.x) * wrld.zoom,
-      (tl.1 as f32 + wrld.scroll_pos.y) * wrld.zoom,
-      WHITE,
-      DrawTextureParams {
-        dest_size: Some(vec2(width, height)),
-        ..Default::default()
-      },
-    );
-    if DEBUG.draw_rects {
-      match self.kind {
-        TowerType::BlockerDown | TowerType::BlockerUp => self.rect.debug_draw(RED),
-        _ => self.rect.debug_draw(PINK),
+    let tiers = defs.clone().map(|_, def| {
+      let mut tiers = vec![def.base.clone()];
+      for upgrade in &def.tiers {
+        let tier = tiers[tiers.len() - 1].upgraded(upgrade);
+        tiers.push(tier);
       }
-    }
+      tiers
+    });
+    Ok(TowerTable { defs, tiers })
   }
-  pub fn get_spawn(&mut self, wrld: &World) -> Option<(EffectKind, (usize, usize))> {
-    if let Some(s) = &mut self.spawn {
-      s.timer -= wrld.dt;
-      if s.timer <= 0.0 {
-        s.reset_timer();
-        return Some((s.kind.clone(), s.pos));
-      }
-    }
-    None
+  pub fn def(&self, kind: TowerType) -> &TowerDef {
+    &self.defs[kind]
   }
-  pub fn new(textures: &Textures, kind: TowerType, grid_pos: (usize, usize)) -> Tower {
-    let pos = grid_pos_to_pos(&grid_pos);
-
-    //
-    //
-    //
-    //
-    //
-    //
-    //
-    //
-    //
-    //
-    //
-    //
-    //
-    // TOWERS
-
-    match kind {
-      TowerType::BlockerDown => Tower {
-        grid_pos,
-        kind,
-        draw_pos: Rect::new(
-          grid_pos.0 * 32,
-          (grid_pos.1 + 1) * 32,
-          (grid_pos.0 + 1) * 32,
-          (grid_pos.1 + 3) * 32,
-        ),
-        rect: Rect::new(pos.0, pos.1 + 32, pos.0 + 32, pos.1 + 32 * 3),
-        texture: textures.blocker_down,
-        trigger: None,
-        atlas: None,
-        direction: Dir::Down,
-        spawn: None,
-        count: 0,
-      },
-      TowerType::BlockerUp => Tower {
-        grid_pos,
-        kind,
-        draw_pos: Rect::new(
-          grid_pos.0 * 32,
-          (grid_pos.1 - 2) * 32,
-          (grid_pos.0 + 1) * 32,
-          grid_pos.1 * 32,
-        ),
-        // draw_pos: Rect::new(grid_pos.0, grid_pos.1 - 2, grid_pos.0 + 1, grid_pos.1),
-        rect: Rect::new(pos.0, pos.1 - 64, pos.0 + 32, pos.1),
-        texture: textures.blocker_up,
-        trigger: None,
-        atlas: None,
-        direction: Dir::Up,
-        spawn: None,
-        count: 0,
-      },
-      TowerType::Lava => Tower {
-        grid_pos,
-        kind,
-        draw_pos: Rect::new(
-          grid_pos.0 * 32,
-          grid_pos.1 * 32 + 20,
-          (grid_pos.0 + 1) * 32,
-          (grid_pos.1 + 1) * 32 + 20,
-        ),
-        rect: Rect::new(pos.0, pos.1 + 32, pos.0 + 32, pos.1 + 2 * 32),
-        texture: textures.tower_lava[0],
-        trigger: None,
-        atlas: Some(FrameDrawing::new(textures.tower_lava.clone(), 0.4)),
-        direction: Dir::Down,
-        spawn: Some(EffectSpawnData {
-          kind: EffectKind::LavaDrop,
-          pos: (grid_pos.0, grid_pos.1 + 1),
-          timer: 0.0,
-          time: 3.0,
-        }),
-        count: 0,
-      },
-      _ => panic!("Unimplemented tower type."),
-    }
-
-    //
-    //
-    //
-    //
-    //
-    //
-    //
-    //
-    //
-    //
-    //
-    //
-    //
-    //
-    //
-    //
-    //
+  /** Tier `tier` of `kind`, or its last tier past the end. */
+  pub fn get(&self, kind: TowerType, tier: usize) -> &TowerTier {
+    let tiers = &self.tiers[kind];
+    &tiers[tier.min(tiers.len() - 1)]
   }
-}
-
-impl Display for Tower {
-  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-    write!(f, "{:?}@{:?}", self.kind, self.grid_pos)
+  pub fn tier_count(&self, kind: TowerType) -> usize {
+    self.tiers[kind].len()
   }
-}
-
-impl Collidable for Tower {
-  fn collide(&self, other: &impl Collidable) -> bool {
-    match self.kind {
-      TowerType::BlockerDown | TowerType::BlockerUp => self.rect.collide(other),
-      _ => false,
-    }
+  /** Price of building `kind` in levels with an economy that do not set their own. */
+  pub fn build_cost(&self, kind: TowerType) -> usize {
+    self.defs[kind].base.cost
   }
-  fn get_hitbox(&self) -> &Rect {
-    &self.rect
+  /** What the tiers up to and including `tier` cost to upgrade through. */
+  pub fn upgrade_costs(&self, kind: TowerType, tier: usize) -> usize {
+    self.tiers[kind]
+      .iter()
+      .take(tier + 1)
+      .skip(1)
+      .map(|t| t.cost)
+      .sum()
   }
 }
 
@@ -273,90 +206,173 @@ impl Collidable for Tower {
 pub struct Towers {
   towers: Vec<Option<Tower>>,
   counts: EnumMap<TowerType, usize>,
+  /** Towers the player built on this level, picking one up again does not take it back. */
+  built: usize,
+  /** Tile index of every tower, by the cells its hitbox covers. */
+  index: SpatialHash<usize>,
 }
 impl Towers {
+  /** Starts with the towers painted into the level. */
   pub fn new(wrld: &World) -> Towers {
-    Towers {
+    let mut towers = Towers {
       towers: repeat_with(|| None)
         .take(wrld.tiles.len())
         .collect::<Vec<_>>(),
       counts: enum_map! { _ => 0 },
-    }
-  }
-  pub fn get_collided_tower(&self, other: &Rect) -> &Option<Tower> {
-    let twr = self.towers.iter().find(|t| {
-      if let Some(tower) = t {
-        let c = tower.collide(other);
-        // println!("Collide: {:?}: {}", tower.kind, c);
-        c
-      } else {
-        false
+      built: 0,
+      index: SpatialHash::new(32),
+    };
+    for (tile_index, tile) in wrld.tiles.iter().enumerate() {
+      if let Some(kind) = tile.kind().placed_tower() {
+        let mut tower = Tower::new(kind, tile.grid_pos(), &wrld.get_lvl().tower_table);
+        tower.base_mut().placed = true;
+        towers.index.insert(tile_index, tower.get_hitbox());
+        towers.towers[tile_index] = Some(tower);
       }
-    });
-    match twr {
-      Some(t) => t,
-      _ => &None,
     }
+    towers
+  }
+  pub fn get_tower(&self, wrld: &World, grid_pos: (usize, usize)) -> Option<&Tower> {
+    wrld.get_tile(&grid_pos.0, &grid_pos.1)?;
+    self.towers[wrld.get_tile_index(&grid_pos.0, &grid_pos.1)].as_ref()
+  }
+  pub fn get_collided_tower(&self, other: &Rect) -> Option<&Tower> {
+    self.get_collided_towers(other).next()
+  }
+  /** Every tower `other` runs into, in no particular order. */
+  pub fn get_collided_towers(&self, other: &Rect) -> impl Iterator<Item = &Tower> + '_ {
+    let other = *other;
+    self
+      .index
+      .query(&other)
+      .into_iter()
+      .filter_map(move |i| self.towers[i].as_ref())
+      .filter(move |tower| tower.collide(&other))
   }
 
-  pub fn tower_dir(kind: &TowerType) -> Dir {
-    match kind {
-      TowerType::BlockerDown => Dir::Down,
-      TowerType::BlockerUp => Dir::Up,
-      TowerType::Collector => Dir::Up,
-      TowerType::Lava => Dir::Down,
-    }
+  /** Towers of `kind` the player built, the ones that use up supply. */
+  pub fn get_tower_count(&self, kind: &TowerType) -> usize {
+    self.counts[*kind]
+  }
+  pub fn get_total_count(&self) -> usize {
+    self.counts.values().sum()
+  }
+  /** Towers the player built over the level, including the ones picked up since. */
+  pub fn get_built_count(&self) -> usize {
+    self.built
   }
-  pub fn get_spawns(&mut self, wrld: &World) -> Vec<(EffectKind, (usize, usize))> {
+  /** Enemies caught by all collectors. */
+  pub fn get_caught_count(&self) -> usize {
     self
       .towers
-      .iter_mut()
-      .filter_map(|t| {
-        if let Some(tower) = t {
-          tower.get_spawn(wrld)
+      .iter()
+      .flatten()
+      .map(|tower| tower.get_count())
+      .sum()
+  }
+  /**
+   * Removes the enemies collectors catch, collectors in tile order and enemies in spawn order so
+   * a full collector always turns away the same ones.
+   */
+  pub fn collect(&mut self, wrld: &mut World, enemies: &mut Vec<Enemy>) {
+    for tower in self.towers.iter_mut().flatten() {
+      enemies.retain(|enemy| {
+        if tower.catch(enemy.get_rect()) {
+          wrld.reward_kill();
+          false
         } else {
-          None
+          true
         }
-      })
+      });
+    }
+  }
+  /** Ages every tower. Returns the effects they spawn this tick. */
+  pub fn update(&mut self, dt: f32) -> Vec<(EffectKind, (usize, usize))> {
+    self
+      .towers
+      .iter_mut()
+      .flatten()
+      .filter_map(|tower| tower.update(dt))
       .collect()
   }
-  pub fn get_tower_count(&self, kind: &TowerType) -> usize {
-    self.counts[*kind]
-    // self
-    //   .towers
-    //   .iter()
-    //   .filter(|t| match t {
-    //     Some(t) => t.kind == *kind,
-    //     _ => false,
-    //   })
-    //   .count()
+  pub fn draw(&self, texs: &Textures, camera: &Camera) {
+    for tower in self.towers.iter().flatten() {
+      if camera.is_visible(tower.get_draw_rect()) {
+        tower.draw(texs);
+      }
+    }
   }
-  pub fn update(&mut self, wrld: &mut World) {
-    for tower in &mut self.towers {
-      if let Some(t) = tower {
-        t.draw(wrld);
+  /**
+   * Picks up the player's tower on the given tile and gives back its supply, its cost and its
+   * upgrades. Pending effect spawns go with it. Towers painted into the level stay, and so does
+   * everything on levels without refunds.
+   */
+  pub fn remove(&mut self, wrld: &mut World, grid_pos: (usize, usize)) {
+    let (x, y) = grid_pos;
+    if !wrld.get_lvl().refunds || wrld.get_tile(&x, &y).is_none() {
+      return;
+    }
+    let tile_index = wrld.get_tile_index(&x, &y);
+    if let Some(tower) = &self.towers[tile_index] {
+      if tower.is_placed() {
+        return;
       }
+      let kind = tower.get_kind();
+      self.counts[kind] -= 1;
+      wrld.earn(wrld.get_lvl().get_tower_cost(&kind));
+      wrld.refund_upgrades(kind, tower.get_tier());
+      self.index.remove(tile_index, tower.get_hitbox());
+      self.towers[tile_index] = None;
     }
-    if !is_mouse_button_released(MouseButton::Left) {
+  }
+
+  /** Moves the tower on the given tile up a tier, if there is one and the player can pay. */
+  pub fn upgrade(&mut self, wrld: &mut World, grid_pos: (usize, usize)) {
+    let (x, y) = grid_pos;
+    if wrld.get_tile(&x, &y).is_none() {
       return;
     }
-    if let Some(mouse_tile) = wrld.get_mouse_tile() {
-      if let Some(selected_kind) = wrld.selected_tower_type {
-        let kind = mouse_tile.kind();
-        if kind == &TileType::BuildDown || kind == &TileType::BuildUp {
-          let (x, y) = &mouse_tile.grid_pos();
-          let tile_index = wrld.get_tile_index(x, y);
-          if self.towers[tile_index].is_none() {
-            //Buildable tile. Check type validity.
-            let is_valid = match Towers::tower_dir(&selected_kind) {
-              Dir::Up => kind == &TileType::BuildUp,
-              _ => kind == &TileType::BuildDown,
-            };
-            if is_valid {
-              self.counts[selected_kind] += 1;
-              wrld.select_tower_kind(&self, &selected_kind);
-              self.towers[tile_index] = Some(Tower::new(&wrld.textures, selected_kind, (*x, *y)));
-            }
+    let tile_index = wrld.get_tile_index(&x, &y);
+    if let Some(tower) = &mut self.towers[tile_index] {
+      let table = &wrld.get_lvl().tower_table;
+      let next = tower.get_tier() + 1;
+      if next >= table.tier_count(tower.get_kind()) {
+        return;
+      }
+      let cost = table.get(tower.get_kind(), next).cost;
+      if wrld.pay_upgrade(cost) {
+        self.index.remove(tile_index, tower.get_hitbox());
+        tower.set_tier(next, &wrld.get_lvl().tower_table);
+        self.index.insert(tile_index, tower.get_hitbox());
+      }
+    }
+  }
+
+  /** Builds the selected tower kind on the given tile, if the tile allows it. */
+  pub fn place(&mut self, wrld: &mut World, grid_pos: (usize, usize)) {
+    let selected_kind = match wrld.selected_tower_type {
+      Some(kind) => kind,
+      None => return,
+    };
+    let (x, y) = grid_pos;
+    if let Some(tile) = wrld.get_tile(&x, &y) {
+      let kind = tile.kind();
+      if kind == &TileType::BuildDown || kind == &TileType::BuildUp {
+        let tile_index = wrld.get_tile_index(&x, &y);
+        if self.towers[tile_index].is_none() {
+          //Buildable tile. Check type validity.
+          let is_valid = match wrld.get_lvl().tower_table.def(selected_kind).direction {
+            Dir::Up => kind == &TileType::BuildUp,
+            _ => kind == &TileType::BuildDown,
+          };
+          if is_valid && wrld.can_build(self, &selected_kind) {
+            self.counts[selected_kind] += 1;
+            self.built += 1;
+            wrld.spend(wrld.get_lvl().get_tower_cost(&selected_kind));
+            wrld.select_tower_kind(self, &selected_kind);
+            let tower = Tower::new(selected_kind, (x, y), &wrld.get_lvl().tower_table);
+            self.index.insert(tile_index, tower.get_hitbox());
+            self.towers[tile_index] = Some(tower);
           }
         }
       }
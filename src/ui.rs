@@ -1,12 +1,17 @@
+use enum_map::Enum;
 use macroquad::{
-  prelude::{is_mouse_button_pressed, Color, MouseButton, Vec2, RED, WHITE},
+  prelude::{Color, Vec2, RED, WHITE},
   shapes::draw_rectangle,
   text::{draw_text, draw_text_ex, TextParams},
-  texture::{draw_texture_ex, DrawTextureParams},
+  texture::DrawTextureParams,
 };
 
 use crate::{
+  bindings::{Action, Bindings},
+  buildable::Buildable,
+  loading::Textures,
   tower::{TowerType, Towers},
+  view::View,
   wrld::World,
 };
 
@@ -17,10 +22,89 @@ const BTN_BOX: f32 = UI_WIDTH / 3.0;
 const BTN_SIZE: f32 = BTN_BOX - 2.0 * BTN_MARGIN;
 const UNAVAILABLE_COLOR: Color = Color::new(0.4, 0.4, 0.4, 1.0);
 
-pub fn draw(wrld: &mut World, towers: &Towers) {
-  let x_offset = wrld.scroll_pos.x * wrld.zoom;
-  let y_offset = wrld.scroll_pos.y * wrld.zoom + 30.0;
-  let check_mouse = true;
+/** Screen offset of the panel, it sits left of the level and moves along with it. */
+fn offset(view: &View) -> (f32, f32) {
+  let level_tl = view.camera.world_to_screen(Vec2::ZERO);
+  (level_tl.x, level_tl.y + 30.0)
+}
+
+/** Top left corner of a tower button, relative to the panel offset. */
+fn button_pos(twr: TowerType) -> (f32, f32) {
+  let btns_per_row = (UI_WIDTH / BTN_BOX).floor();
+  let i = twr.into_usize() as f32;
+  let row = (i / btns_per_row).floor();
+  (
+    -UI_WIDTH + (i % btns_per_row) * BTN_BOX,
+    UI_HEIGHT - BTN_BOX - row * BTN_BOX,
+  )
+}
+
+fn remaining_count(wrld: &World, towers: &Towers, twr: TowerType) -> usize {
+  wrld
+    .get_lvl()
+    .get_tower_supply(&twr)
+    .saturating_sub(towers.get_tower_count(&twr))
+}
+
+/** The price in levels with an economy, otherwise how many are left. */
+fn button_label(wrld: &World, towers: &Towers, twr: TowerType) -> String {
+  match wrld.get_lvl().economy {
+    Some(_) => format!("${}", wrld.get_lvl().get_tower_cost(&twr)),
+    None => format!("{}", remaining_count(wrld, towers, twr)),
+  }
+}
+
+/** Returns the tower button under the cursor, if one more of its kind can be built. */
+pub fn button_at(wrld: &World, towers: &Towers, view: &View) -> Option<TowerType> {
+  let (x_offset, y_offset) = offset(view);
+  let mp_x = view.mouse_pos.0 - x_offset;
+  let mp_y = view.mouse_pos.1 - y_offset;
+
+  (0..TowerType::LENGTH)
+    .map(TowerType::from_usize)
+    .find(|&twr| {
+      let (x, y) = button_pos(twr);
+      let inside = mp_x > x && mp_x < x + BTN_BOX && mp_y > y && mp_y < y + BTN_BOX;
+      inside && wrld.can_build(towers, &twr)
+    })
+}
+
+/** Tier and upgrade price of the selected tower. */
+fn draw_selection(wrld: &World, towers: &Towers, view: &View, bindings: &Bindings) {
+  let tower = match view.selected_tower.and_then(|g| towers.get_tower(wrld, g)) {
+    Some(tower) => tower,
+    None => return,
+  };
+  let (x_offset, y_offset) = offset(view);
+  let kind = tower.get_kind();
+  let table = &wrld.get_lvl().tower_table;
+  let tiers = table.tier_count(kind);
+  let upgrade = if tower.get_tier() + 1 >= tiers {
+    "Fully upgraded".to_owned()
+  } else {
+    let price = match wrld.get_lvl().economy {
+      Some(_) => format!("${}", table.get(kind, tower.get_tier() + 1).cost),
+      None => format!("{} left", wrld.upgrades),
+    };
+    format!("{}: upgrade, {}", bindings.describe(Action::Upgrade), price)
+  };
+  let lines = [
+    format!("{} {}/{}", kind.id(), tower.get_tier() + 1, tiers),
+    upgrade,
+  ];
+  for (i, line) in lines.iter().enumerate() {
+    draw_text(
+      line,
+      -UI_WIDTH + x_offset,
+      50.0 + 20.0 * i as f32 + y_offset,
+      20.0,
+      WHITE,
+    );
+  }
+}
+
+pub fn draw(wrld: &World, towers: &Towers, texs: &Textures, view: &View, bindings: &Bindings) {
+  let (x_offset, y_offset) = offset(view);
   draw_rectangle(
     -UI_WIDTH - 7.0 + x_offset,
     y_offset,
@@ -42,66 +126,48 @@ pub fn draw(wrld: &mut World, towers: &Towers) {
     24.0,
     WHITE,
   );
+  if wrld.get_lvl().economy.is_some() {
+    draw_text(
+      &format!("Money: {}", wrld.money),
+      -UI_WIDTH / 2.0 + x_offset,
+      20.0 + y_offset,
+      24.0,
+      WHITE,
+    );
+  }
 
-  let btns_per_row = (UI_WIDTH / BTN_BOX).floor();
-  let mut i: f32 = 0.0;
-  let mut row: f32 = 0.0;
-  let mut try_select_kind: Option<TowerType> = None;
-  let mp_x = wrld.mouse_pos.0 - x_offset;
-  let mp_y = wrld.mouse_pos.1 - y_offset;
+  let hovered = button_at(wrld, towers, view);
 
-  for (twr, &texs) in &wrld.textures.tower_buttons {
-    let x = -UI_WIDTH + (i % btns_per_row) * BTN_BOX;
-    let y = UI_HEIGHT - BTN_BOX - row * BTN_BOX;
-    let remaining_count = wrld.get_lvl().get_tower_supply(&twr) - towers.get_tower_count(&twr);
-    let highlighted = if check_mouse && remaining_count > 0 {
-      let inside = mp_x > x && mp_x < x + BTN_BOX && mp_y > y && mp_y < y + BTN_BOX;
-      if inside && is_mouse_button_pressed(MouseButton::Left) {
-        try_select_kind = Some(twr);
-      }
-      inside
-    } else {
-      false
-    };
-    let t = match (
-      highlighted,
-      wrld.selected_tower_type == Some(twr) && remaining_count > 0,
+  for twr in (0..TowerType::LENGTH).map(TowerType::from_usize) {
+    let (x, y) = button_pos(twr);
+    let available = wrld.can_build(towers, &twr);
+    let state = match (
+      hovered == Some(twr),
+      wrld.selected_tower_type == Some(twr) && available,
     ) {
-      (false, false) => texs.normal,
-      (true, false) => texs.highlighted,
-      _ => texs.selected,
+      (false, false) => "",
+      (true, false) => "_highlight",
+      _ => "_selected",
     };
-    // println!("{:?}, x{} y{}", twr, x, y);
-    draw_texture_ex(
-      t,
+    texs.get(&format!("{}_btn{}", twr.id(), state)).draw(
       x + x_offset,
       y + BTN_MARGIN + y_offset,
-      if remaining_count > 0 {
-        WHITE
-      } else {
-        UNAVAILABLE_COLOR
-      },
+      if available { WHITE } else { UNAVAILABLE_COLOR },
       DrawTextureParams {
         dest_size: Some(Vec2::new(BTN_SIZE, BTN_SIZE)),
         ..Default::default()
       },
     );
     draw_text_ex(
-      &format!("{}", remaining_count),
+      &button_label(wrld, towers, twr),
       x + x_offset + 3.0,
       y + BTN_MARGIN + y_offset + BTN_SIZE - 5.0,
       TextParams {
         font_size: 30,
-        color: if remaining_count > 0 { WHITE } else { RED },
+        color: if available { WHITE } else { RED },
         ..Default::default()
       },
     );
-    i += 1.0;
-    if i % btns_per_row == 0.0 {
-      row += 1.0;
-    }
-  }
-  if let Some(twr) = try_select_kind {
-    wrld.select_tower_kind(towers, &twr);
   }
+  draw_selection(wrld, towers, view, bindings);
 }
@@ -0,0 +1,162 @@
+use macroquad::{
+  prelude::{mouse_position, mouse_wheel, vec2},
+  time::get_frame_time,
+  window::{screen_height, screen_width},
+};
+
+use crate::{
+  bindings::{Action, Bindings},
+  camera::Camera,
+  deb::DEBUG,
+  enemy::Corpse,
+  level::Level,
+  sim::Event,
+  ui::UI_WIDTH,
+};
+
+/** Level pixels per second the arrow keys move the camera. */
+const BASE_MOVEMENT_SPEED: f32 = 300.0;
+/** Zoom factor of one mouse wheel step. */
+const ZOOM_STEP: f32 = 1.25;
+/** Screen pixels the cursor may move while dragging and still count as a click. */
+const CLICK_SLOP: f32 = 4.0;
+
+/**
+ * Presentation state: the camera, the cursor, the selection and the dying enemies. Never read by
+ * the simulation.
+ */
+pub struct View {
+  mouse_grid: Option<(usize, usize)>,
+  prev_mouse_pos: (f32, f32),
+  /** How far the current drag has moved the cursor, in screen pixels. */
+  drag_distance: f32,
+
+  pub camera: Camera,
+  /** Tile of the built tower the player clicked, the target of upgrades. */
+  pub selected_tower: Option<(usize, usize)>,
+  pub mouse_pos: (f32, f32),
+  /** Enemies that died recently, still playing their death animation. */
+  pub corpses: Vec<Corpse>,
+}
+
+impl View {
+  pub fn new() -> View {
+    let pos = if DEBUG.zero_offset_initial_camera {
+      vec2(0.0, 0.0)
+    } else {
+      vec2(-(UI_WIDTH + 32.0), 0.0)
+    };
+    View {
+      camera: Camera::new(pos, vec2(screen_width(), screen_height())),
+      mouse_pos: (0.0, 0.0),
+      prev_mouse_pos: (0.0, 0.0),
+      drag_distance: 0.0,
+      selected_tower: None,
+      mouse_grid: None,
+      corpses: Vec::new(),
+    }
+  }
+
+  pub fn get_mouse_grid(&self) -> Option<(usize, usize)> {
+    self.mouse_grid
+  }
+  /** Whether the last drag moved the view rather than being a click. */
+  pub fn was_dragged(&self) -> bool {
+    self.drag_distance > CLICK_SLOP
+  }
+  /** The tile under screen position `pos`, if it is inside the level. */
+  pub fn px_to_grid(&self, lvl: &Level, pos: (f32, f32)) -> Option<(usize, usize)> {
+    let world = self.camera.screen_to_world(vec2(pos.0, pos.1));
+    let width = lvl.width as f32 * 32.0;
+    let height = lvl.height as f32 * 32.0;
+    if world.x < 0.0 || world.x >= width || world.y < 0.0 || world.y >= height {
+      //Cursor is out of bounds.
+      None
+    } else {
+      Some(((world.x / 32.0) as usize, (world.y / 32.0) as usize))
+    }
+  }
+
+  fn update_mouse(&mut self, lvl: &Level) {
+    self.prev_mouse_pos = self.mouse_pos;
+    self.mouse_pos = mouse_position();
+
+    //Calc grid position.
+    self.mouse_grid = self.px_to_grid(lvl, self.mouse_pos);
+  }
+  fn update_panning(&mut self, bindings: &Bindings) {
+    if bindings.is_pressed(Action::PanDrag) || bindings.is_pressed(Action::Remove) {
+      self.drag_distance = 0.0;
+    }
+    if bindings.is_down(Action::PanDrag) {
+      let delta = vec2(
+        self.mouse_pos.0 - self.prev_mouse_pos.0,
+        self.mouse_pos.1 - self.prev_mouse_pos.1,
+      );
+      self.drag_distance += delta.length();
+      self.camera.drag(delta);
+    } else {
+      let step = BASE_MOVEMENT_SPEED * get_frame_time();
+      let x_vel = match (
+        bindings.is_down(Action::PanLeft),
+        bindings.is_down(Action::PanRight),
+      ) {
+        (true, false) => -step,
+        (false, true) => step,
+        _ => 0f32,
+      };
+      let y_vel = match (
+        bindings.is_down(Action::PanUp),
+        bindings.is_down(Action::PanDown),
+      ) {
+        (true, false) => -step,
+        (false, true) => step,
+        _ => 0f32,
+      };
+      self.camera.pos += vec2(x_vel, y_vel);
+    }
+  }
+  fn update_zoom(&mut self) {
+    //Positive; scroll up, negative; scroll down.
+    let y = mouse_wheel().1;
+    if y != 0.0 {
+      let zoom = if y > 0.0 {
+        self.camera.zoom * ZOOM_STEP
+      } else {
+        self.camera.zoom / ZOOM_STEP
+      };
+      let cursor = vec2(self.mouse_pos.0, self.mouse_pos.1);
+      self.camera.zoom_at(cursor, zoom);
+    }
+  }
+
+  /** Reads mouse and keyboard to move the camera. Call once per rendered frame. */
+  pub fn update(&mut self, lvl: &Level, bindings: &Bindings) {
+    self.camera.screen = vec2(screen_width(), screen_height());
+    self.update_mouse(lvl);
+    self.update_zoom();
+    self.update_panning(bindings);
+    self
+      .camera
+      .clamp(vec2(lvl.width as f32 * 32.0, lvl.height as f32 * 32.0));
+    //The camera may have moved since the cursor was read.
+    self.mouse_grid = self.px_to_grid(lvl, self.mouse_pos);
+  }
+
+  /** Follows a simulation tick of `dt` seconds that raised `events`. Call after every tick. */
+  pub fn follow_tick(&mut self, lvl: &Level, events: &[Event], dt: f32) {
+    self.corpses.retain_mut(|corpse| corpse.update(dt));
+    for event in events {
+      let Event::EnemyDied { kind, pos, facing } = event;
+      if let Some(def) = lvl.enemy_table.get(kind) {
+        self.corpses.push(Corpse::new(def, *pos, *facing));
+      }
+    }
+  }
+}
+
+impl Default for View {
+  fn default() -> Self {
+    View::new()
+  }
+}